@@ -0,0 +1,90 @@
+use std::{convert::TryFrom, error::Error, process};
+
+use clap::Parser;
+
+use lib::common::{
+    http_client::Url,
+    paths,
+    request::{CsvRequest, RawRequest},
+};
+use lib::loadgen::runner::{self, LoadGenConfig};
+
+/// Replays a requests file (csv format) against the bookings REST API,
+/// reporting throughput, end-to-end latency and errors.
+#[derive(Parser)]
+#[clap(name = "loadgen")]
+struct Args {
+    /// Requests file (csv format)
+    #[clap(default_value = paths::DEFAULT_REQUESTS)]
+    requests: String,
+
+    /// Base URL of the bookings API
+    #[clap(long, default_value = "http://localhost:8080")]
+    url: String,
+
+    /// Maximum requests submitted per second (unlimited by default)
+    #[clap(long)]
+    rate: Option<f64>,
+
+    /// Maximum bookings in flight (submitted but not completed)
+    #[clap(long, default_value_t = 10)]
+    concurrency: usize,
+
+    /// Time between status polls, in ms
+    #[clap(long, default_value_t = 500)]
+    poll_interval: u64,
+
+    /// Time to wait for each booking to complete, in secs
+    #[clap(long, default_value_t = 300)]
+    timeout: u64,
+
+    /// Timeout for each HTTP call, in ms
+    #[clap(long, default_value_t = 5000)]
+    http_timeout: u64,
+}
+
+fn read_requests(path: &str) -> Result<Vec<RawRequest>, Box<dyn Error>> {
+    let mut rdr = csv::Reader::from_path(path)?;
+    let mut requests = Vec::new();
+    for result in rdr.deserialize::<CsvRequest>() {
+        requests.push(RawRequest::try_from(result?)?);
+    }
+    Ok(requests)
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    if let Some(rate) = args.rate {
+        if rate <= 0.0 {
+            return Err(format!("Invalid rate: {} (must be positive)", rate).into());
+        }
+    }
+
+    let requests = read_requests(&args.requests)?;
+    println!(
+        "Replaying {} requests from {} against {}",
+        requests.len(),
+        args.requests,
+        args.url
+    );
+
+    let config = LoadGenConfig {
+        url: Url::parse(&args.url)?,
+        rate: args.rate,
+        concurrency: args.concurrency,
+        poll_interval: args.poll_interval,
+        booking_timeout: args.timeout,
+        http_timeout: args.http_timeout,
+    };
+    let report = runner::run(config, requests);
+    println!("{}", report);
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        println!("{}", err);
+        process::exit(1);
+    }
+}
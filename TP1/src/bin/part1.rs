@@ -1,57 +1,148 @@
-use std::{env, error::Error, process};
+use std::{error::Error, process};
 
-use lib::common::{config::GeneralConfig, paths};
+use clap::Parser;
+
+use lib::common::{
+    config::{Config, GeneralConfig, Part},
+    paths, utils,
+};
 
 use lib::part1::{
     airlines, dispatcher, hotel,
+    input::Input,
     logger::{self, LoggerSender},
     metrics_collector,
+    rejects::RejectsWriter,
     request_handler::RequestHandler,
+    results::ResultsWriter,
+    shutdown::Shutdown,
 };
 
-fn get_requests_path(logger: LoggerSender) -> String {
-    let args: Vec<String> = env::args().collect();
-    match args.get(1) {
+/// AlGlobo.com bookings processor (threads implementation).
+#[derive(Parser)]
+#[clap(name = "part1")]
+struct Args {
+    /// Requests file (csv format), use `-` to read from stdin
+    requests: Option<String>,
+
+    /// Keep reading the requests file as it grows (like `tail -f`). Only appends and truncations
+    /// are followed: a file replaced by another one (e.g. rotated) is not reopened
+    #[clap(long)]
+    follow: bool,
+
+    /// Write the result of each request to this file (JSON lines if it ends in .jsonl, csv otherwise)
+    #[clap(long)]
+    results: Option<String>,
+
+    /// Write the rejected rows (line, reason and content) to this file [default: <logger dirpath>/part1-rejects-<date>.csv]
+    #[clap(long)]
+    rejects: Option<String>,
+
+    /// Directory containing the configuration files
+    #[clap(long, env = "CONFIG_DIR", default_value = paths::DEFAULT_CONFIG_DIR)]
+    config_dir: String,
+}
+
+fn get_requests_input(
+    requests: Option<String>,
+    follow: bool,
+    logger: LoggerSender,
+) -> Result<Input, Box<dyn Error>> {
+    let input = match requests {
         Some(path) => {
-            logger.send(format!("Using requests file received (path: {})", path));
-            path.to_string()
+            let input = Input::new(path, follow)?;
+            logger.send(format!("Using requests from {}", input.describe()));
+            input
         }
         None => {
+            let input = Input::new(String::from(paths::DEFAULT_REQUESTS), follow)?;
             logger.send(format!(
-                "No requests file received, using default one (path: {})",
-                paths::DEFAULT_REQUESTS
+                "No requests file received, using default one from {}",
+                input.describe()
             ));
-            String::from(paths::DEFAULT_REQUESTS)
+            input
         }
-    }
+    };
+    Ok(input)
 }
 
 fn run() -> Result<(), Box<dyn Error>> {
-    let GeneralConfig {
-        port: _,
-        logger_config,
-        metrics_collector_config,
-    } = GeneralConfig::from_path(paths::GENERAL_CONFIG)?;
+    let args = Args::parse();
+    let Config {
+        general:
+            GeneralConfig {
+                port: _,
+                logger_config,
+                metrics_collector_config,
+                request_handler_config,
+                seed,
+                ..
+            },
+        airlines: airlines_config,
+        hotel: hotel_config,
+    } = Config::load(&args.config_dir, Part::One)?;
 
+    let rejects_path = args.rejects.unwrap_or_else(|| {
+        format!(
+            "{}/part1-rejects-{}.csv",
+            logger_config.dirpath,
+            utils::now_rfc()
+        )
+    });
     let logger = logger::Logger::from_config(logger_config)?;
-    let path = get_requests_path(logger.get_sender());
+    let shutdown = Shutdown::new();
+    shutdown.install_handler()?;
+    let input = get_requests_input(args.requests, args.follow, logger.get_sender())?;
+    if let Some(seed) = seed {
+        logger.get_sender().send(format!(
+            "Using seed {} for the webservices simulation",
+            seed
+        ));
+    }
 
-    let metrics_collector =
-        metrics_collector::MetricsCollector::from_config(metrics_collector_config)?;
-    let airlines = airlines::from_path(paths::AIRLINES_CONFIG, logger.get_sender())?;
-    let hotel = hotel::from_path(paths::HOTEL_CONFIG, logger.get_sender())?;
+    let airlines =
+        airlines::from_config(airlines_config, seed, logger.get_sender(), shutdown.clone());
+    let hotel = hotel::from_config(hotel_config, seed, logger.get_sender(), shutdown.clone());
+    let mut webservices: Vec<_> = airlines.values().cloned().collect();
+    webservices.push(hotel.clone());
+    let metrics_collector = metrics_collector::MetricsCollector::from_config(
+        metrics_collector_config,
+        airlines::inventories(&airlines),
+        webservices,
+        logger.get_sender(),
+    )?;
+    let results_writer = match &args.results {
+        Some(path) => {
+            logger
+                .get_sender()
+                .send(format!("Writing requests results to {}", path));
+            Some(ResultsWriter::from_path(path)?)
+        }
+        None => None,
+    };
     let mut req_handler = RequestHandler::new(
+        request_handler_config,
         airlines,
         hotel,
         logger.get_sender(),
         metrics_collector.get_sender(),
+        results_writer.as_ref().map(ResultsWriter::get_sender),
     );
 
-    dispatcher::from_path(path, &mut req_handler)?;
+    dispatcher::from_input(
+        &input,
+        &mut req_handler,
+        RejectsWriter::new(rejects_path, logger.get_sender()),
+        &logger.get_sender(),
+        &shutdown,
+    )?;
 
     req_handler.join();
-    logger.join();
+    if let Some(results_writer) = results_writer {
+        results_writer.join();
+    }
     metrics_collector.join();
+    logger.join();
     Ok(())
 }
 
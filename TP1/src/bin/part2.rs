@@ -1,39 +1,113 @@
-use std::env;
+use std::process;
 
-use actix::Actor;
+use actix::{Actor, Supervisor};
 use actix_cors::Cors;
-use actix_web::{web::Data, App, HttpServer};
+use actix_web::{rt, web, web::Data, App, HttpServer};
+use clap::Parser;
 
-use lib::common::{config::GeneralConfig, paths};
+use lib::common::{
+    config::{AdminConfig, Config, GeneralConfig, HttpMode, Part},
+    paths,
+};
 use lib::part2::{
     logger::Logger,
     metrics::MetricsCollector,
+    offers::OffersService,
     request_handler::RequestHandler,
-    routes::{get_index, get_metrics, get_request, post_request},
+    routes,
     state::ServerState,
     status_service::StatusService,
+    tls::{self, HttpsRedirect},
+    webhooks::WebhookService,
 };
 
+/// AlGlobo.com bookings REST API (actors implementation).
+#[derive(Parser)]
+#[clap(name = "part2")]
+struct Args {
+    /// Directory containing the configuration files
+    #[clap(long, env = "CONFIG_DIR", default_value = paths::DEFAULT_CONFIG_DIR)]
+    config_dir: String,
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let GeneralConfig {
-        port,
-        logger_config,
-        metrics_collector_config,
-    } = GeneralConfig::from_path(paths::GENERAL_CONFIG)
-        .expect("[CRITICAL] Error reading general config");
-
-    let port = match env::var("PORT") {
-        Ok(var) => var.parse::<u16>().expect("[CRITICAL] Invalid PORT env var"),
-        Err(_) => port,
-    };
+    let args = Args::parse();
+    let Config {
+        general:
+            GeneralConfig {
+                port,
+                logger_config,
+                metrics_collector_config,
+                status_service_config,
+                offers_config,
+                webhooks_config,
+                tls_config,
+                admin_config: AdminConfig { token: admin_token },
+                seed,
+                ..
+            },
+        airlines: airlines_config,
+        hotel: hotel_config,
+    } = Config::load(&args.config_dir, Part::Two).unwrap_or_else(|err| {
+        println!("{}", err);
+        process::exit(1);
+    });
 
     let logger = Logger::new(logger_config).start();
-    let metrics_collector = MetricsCollector::new(metrics_collector_config, logger.clone()).start();
-    let status_service = StatusService::new(logger.clone(), metrics_collector.clone()).start();
-    let request_handler = RequestHandler::new(logger.clone(), status_service.clone()).start();
+    if let Some(seed) = seed {
+        Logger::send_to(
+            &logger,
+            format!("Using seed {} for the webservices simulation", seed),
+        );
+    }
+    let metrics_collector = MetricsCollector::new(metrics_collector_config, logger.clone());
+    let metrics_collector = Supervisor::start(|_| metrics_collector);
+    let offers = OffersService::new(offers_config, logger.clone(), metrics_collector.clone());
+    let offers = Supervisor::start(|_| offers);
+    let webhooks_enabled = webhooks_config.enabled();
+    if !webhooks_enabled {
+        Logger::send_to(
+            &logger,
+            String::from("[WARNING] Webhooks disabled (no webhooks secret configured)"),
+        );
+    }
+    let webhooks = WebhookService::new(webhooks_config, logger.clone()).start();
+    let status_service = StatusService::new(
+        status_service_config,
+        logger.clone(),
+        metrics_collector.clone(),
+        offers.clone(),
+        webhooks,
+    );
+    let status_service = Supervisor::start(|_| status_service);
+    let request_handler = RequestHandler::new(
+        airlines_config,
+        hotel_config,
+        seed,
+        webhooks_enabled,
+        logger.clone(),
+        metrics_collector.clone(),
+        status_service.clone(),
+    )
+    .start();
 
-    HttpServer::new(move || {
+    let tls_server_config = match tls_config.enabled {
+        true => Some(tls::load_server_config(&tls_config).unwrap_or_else(|err| {
+            println!("[CRITICAL] Invalid TLS configuration: {}", err);
+            process::exit(1);
+        })),
+        false => None,
+    };
+
+    if admin_token.is_empty() {
+        Logger::send_to(
+            &logger,
+            String::from("[WARNING] Admin routes disabled (no admin token configured)"),
+        );
+    }
+
+    let mut server = HttpServer::new(move || {
         let cors = Cors::permissive();
 
         App::new()
@@ -42,14 +116,33 @@ async fn main() -> std::io::Result<()> {
                 status_service.clone(),
                 logger.clone(),
                 metrics_collector.clone(),
+                offers.clone(),
+                admin_token.clone(),
             )))
             .wrap(cors)
-            .service(get_index)
-            .service(get_metrics)
-            .service(post_request)
-            .service(get_request)
-    })
-    .bind(("0.0.0.0", port))?
-    .run()
-    .await
+            .configure(routes::configure)
+    });
+    if tls_config.http == HttpMode::Serve {
+        server = server.bind(("0.0.0.0", port))?;
+    }
+    if let Some(tls_server_config) = tls_server_config {
+        server = server.bind_rustls(("0.0.0.0", tls_config.port), tls_server_config)?;
+    }
+
+    if tls_config.http == HttpMode::Redirect {
+        let (https_host, https_port) = (tls_config.host, tls_config.port);
+        let redirect_server = HttpServer::new(move || {
+            App::new()
+                .app_data(Data::new(HttpsRedirect::new(
+                    https_host.clone(),
+                    https_port,
+                )))
+                .default_service(web::to(tls::redirect_to_https))
+        })
+        .bind(("0.0.0.0", port))?
+        .run();
+        rt::spawn(redirect_server);
+    }
+
+    server.run().await
 }
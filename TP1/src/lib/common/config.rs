@@ -1,11 +1,46 @@
-use serde::Deserialize;
-use std::error::Error;
+//! System configuration.
+//!
+//! Configuration is read from the JSON files in the config directory, then
+//! every field can be overridden with an environment variable:
+//! * General config: `PORT`, `SEED`, `LOGGER_DIRPATH`, `METRICS_<FIELD>`, `STATUS_<FIELD>`,
+//!   `ARCHIVE_<FIELD>`, `STORE_<FIELD>`, `REQUEST_HANDLER_<FIELD>`, `OFFERS_<FIELD>` (except `tiers`),
+//!   `WEBHOOKS_<FIELD>`, `TLS_<FIELD>` and `ADMIN_TOKEN` (e.g. `METRICS_PRINTER_PERIOD`).
+//! * Hotel config: `HOTEL_<FIELD>` (e.g. `HOTEL_RATE_LIMIT`).
+//! * Airlines config: `AIRLINE_<NAME>_<FIELD>` (except `flights`), where `<NAME>` is the airline
+//!   name in uppercase with non alphanumeric chars replaced by `_`
+//!   (e.g. `AIRLINE_AEROLINEAS_ARGENTINAS_FAILURE_RATE`).
+//!
+//! Only `port`, `logger_config` and `metrics_collector_config` are required in the general
+//! config, every other section has a default. The resulting configuration is validated as a
+//! whole for the part that loads it (part2 only sections are skipped by part1), reporting
+//! every error found.
+
+use std::{collections::HashSet, env, error::Error, fmt, str::FromStr};
+
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::common::paths;
+
+// TYPES ----------------------------------------------------------------------
 
 #[derive(Debug, Deserialize)]
 pub struct GeneralConfig {
     pub port: u16,
     pub logger_config: LoggerConfig,
     pub metrics_collector_config: MetricsCollectorConfig,
+    #[serde(default)]
+    pub status_service_config: StatusServiceConfig,
+    #[serde(default)]
+    pub request_handler_config: RequestHandlerConfig,
+    #[serde(default)]
+    pub offers_config: OffersConfig,
+    #[serde(default)]
+    pub webhooks_config: WebhooksConfig,
+    #[serde(default)]
+    pub tls_config: TlsConfig,
+    #[serde(default)]
+    pub admin_config: AdminConfig,
+    pub seed: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -19,6 +54,104 @@ pub struct MetricsCollectorConfig {
     pub n_most_booked: usize,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RequestHandlerConfig {
+    pub pool_size: usize,
+    pub queue_size: usize,
+}
+
+/// Offers are generated every `refresh_period` ms for the most booked routes, and are
+/// valid for `validity` ms. Each route gets the discount of the first tier it ranks in.
+#[derive(Debug, Deserialize)]
+pub struct OffersConfig {
+    pub refresh_period: u64,
+    pub validity: u64,
+    pub tiers: Vec<DiscountTier>,
+}
+
+/// Discount (percentage) for the routes ranked within the `top` most booked ones.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DiscountTier {
+    pub top: usize,
+    pub discount: u8,
+}
+
+/// Completion webhooks are signed with `secret` (HMAC-SHA256) and delivered up to `max_attempts`
+/// times, waiting `initial_backoff` ms before the first retry and doubling it up to `max_backoff` ms.
+/// Each attempt times out after `timeout` ms. Webhooks are disabled while `secret` is empty.
+#[derive(Debug, Deserialize)]
+pub struct WebhooksConfig {
+    pub secret: String,
+    pub max_attempts: u32,
+    pub initial_backoff: u64,
+    pub max_backoff: u64,
+    pub timeout: u64,
+}
+
+/// When `enabled`, the server is also served over HTTPS on `port`, with the PEM certificate
+/// chain at `cert_path` and its private key at `key_path`. Plain HTTP (on the general `port`)
+/// is then served as usual, redirected to HTTPS (at the public `host`) or disabled, according
+/// to `http`.
+#[derive(Debug, Default, Deserialize)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    #[serde(default)]
+    pub host: String,
+    pub port: u16,
+    pub cert_path: String,
+    pub key_path: String,
+    pub http: HttpMode,
+}
+
+/// The admin routes require an `Authorization: Bearer <token>` header with this `token`.
+/// They are disabled while it is empty.
+#[derive(Debug, Default, Deserialize)]
+pub struct AdminConfig {
+    pub token: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpMode {
+    #[default]
+    Serve,
+    Redirect,
+    Disabled,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusServiceConfig {
+    pub retention_ttl: u64,
+    pub max_entries: usize,
+    pub eviction_period: u64,
+    pub archive_config: ArchiveConfig,
+    #[serde(default)]
+    pub store_config: StoreConfig,
+}
+
+/// Where the request statuses are kept: in `memory` (evicted to the archive once
+/// completed) or in a `sqlite` database at `path`, that keeps the whole history.
+#[derive(Debug, Deserialize)]
+pub struct StoreConfig {
+    pub backend: StoreBackend,
+    #[serde(default)]
+    pub path: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+    Memory,
+    Sqlite,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArchiveConfig {
+    pub dirpath: String,
+    pub max_file_size: u64,
+    pub max_files: usize,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct WebServiceConfig {
     pub name: String,
@@ -27,17 +160,794 @@ pub struct WebServiceConfig {
     pub retry_time: u64,
     pub min_delay: u64,
     pub max_delay: u64,
+    /// Flights with their seat capacity (only airlines). Unlimited seats if not declared.
+    #[serde(default)]
+    pub flights: Option<Vec<FlightConfig>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FlightConfig {
+    pub origin: String,
+    pub destiny: String,
+    pub seats: u32,
 }
 
 pub type HotelConfig = WebServiceConfig;
 pub type AirlineConfig = WebServiceConfig;
 pub type AirlinesConfig = Vec<AirlineConfig>;
 
+/// Part of the system a configuration is loaded for, as each one validates the sections it uses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Part {
+    One,
+    Two,
+}
+
+/// Whole system configuration, loaded from a config directory.
+#[derive(Debug)]
+pub struct Config {
+    pub general: GeneralConfig,
+    pub airlines: AirlinesConfig,
+    pub hotel: HotelConfig,
+}
+
+/// Every error found while loading or validating the configuration.
+#[derive(Debug)]
+pub struct ConfigError {
+    errors: Vec<String>,
+}
+
+// LOADING --------------------------------------------------------------------
+
+impl Config {
+    /// Given a config directory and the part that uses it this method will read every config
+    /// file, apply the environment overrides and validate the result. All the errors are
+    /// reported together.
+
+    pub fn load(config_dir: &str, part: Part) -> Result<Config, ConfigError> {
+        let mut errors = Vec::new();
+
+        let general = read_file::<GeneralConfig>(config_dir, paths::GENERAL_CONFIG, &mut errors);
+        let airlines = read_file::<AirlinesConfig>(config_dir, paths::AIRLINES_CONFIG, &mut errors);
+        let hotel = read_file::<HotelConfig>(config_dir, paths::HOTEL_CONFIG, &mut errors);
+
+        let (mut general, mut airlines, mut hotel) = match (general, airlines, hotel) {
+            (Some(general), Some(airlines), Some(hotel)) => (general, airlines, hotel),
+            _ => return Err(ConfigError { errors }),
+        };
+
+        general.apply_overrides(&mut errors);
+        for airline in airlines.iter_mut() {
+            let prefix = format!("AIRLINE_{}", env_key(&airline.name));
+            airline.apply_overrides(&prefix, &mut errors);
+        }
+        hotel.apply_overrides("HOTEL", &mut errors);
+
+        general.validate(part, &mut errors);
+        validate_airlines(&airlines, &mut errors);
+        hotel.validate(paths::HOTEL_CONFIG, &mut errors);
+        if hotel.flights.is_some() {
+            errors.push(format!(
+                "{}: hotel can not declare flights",
+                paths::HOTEL_CONFIG
+            ));
+        }
+
+        match errors.is_empty() {
+            true => Ok(Config {
+                general,
+                airlines,
+                hotel,
+            }),
+            false => Err(ConfigError { errors }),
+        }
+    }
+}
+
 impl GeneralConfig {
-    pub fn from_path(path: &str) -> Result<GeneralConfig, Box<dyn Error>> {
-        let data = std::fs::read_to_string(path)?;
-        let config: GeneralConfig = serde_json::from_str(&data)?;
+    fn apply_overrides(&mut self, errors: &mut Vec<String>) {
+        let GeneralConfig {
+            port,
+            logger_config,
+            metrics_collector_config,
+            status_service_config,
+            request_handler_config,
+            offers_config,
+            webhooks_config,
+            tls_config,
+            admin_config,
+            seed,
+        } = self;
+        let archive_config = &mut status_service_config.archive_config;
+        let store_config = &mut status_service_config.store_config;
+
+        override_field("PORT", port, errors);
+        override_optional_field("SEED", seed, errors);
+        override_field("LOGGER_DIRPATH", &mut logger_config.dirpath, errors);
+        override_field(
+            "METRICS_PRINTER_PERIOD",
+            &mut metrics_collector_config.printer_period,
+            errors,
+        );
+        override_field(
+            "METRICS_N_MOST_BOOKED",
+            &mut metrics_collector_config.n_most_booked,
+            errors,
+        );
+        override_field(
+            "STATUS_RETENTION_TTL",
+            &mut status_service_config.retention_ttl,
+            errors,
+        );
+        override_field(
+            "STATUS_MAX_ENTRIES",
+            &mut status_service_config.max_entries,
+            errors,
+        );
+        override_field(
+            "STATUS_EVICTION_PERIOD",
+            &mut status_service_config.eviction_period,
+            errors,
+        );
+        override_field("ARCHIVE_DIRPATH", &mut archive_config.dirpath, errors);
+        override_field(
+            "ARCHIVE_MAX_FILE_SIZE",
+            &mut archive_config.max_file_size,
+            errors,
+        );
+        override_field("ARCHIVE_MAX_FILES", &mut archive_config.max_files, errors);
+        override_field("STORE_BACKEND", &mut store_config.backend, errors);
+        override_field("STORE_PATH", &mut store_config.path, errors);
+        override_field(
+            "REQUEST_HANDLER_POOL_SIZE",
+            &mut request_handler_config.pool_size,
+            errors,
+        );
+        override_field(
+            "REQUEST_HANDLER_QUEUE_SIZE",
+            &mut request_handler_config.queue_size,
+            errors,
+        );
+        override_field(
+            "OFFERS_REFRESH_PERIOD",
+            &mut offers_config.refresh_period,
+            errors,
+        );
+        override_field("OFFERS_VALIDITY", &mut offers_config.validity, errors);
+        override_field("WEBHOOKS_SECRET", &mut webhooks_config.secret, errors);
+        override_field(
+            "WEBHOOKS_MAX_ATTEMPTS",
+            &mut webhooks_config.max_attempts,
+            errors,
+        );
+        override_field(
+            "WEBHOOKS_INITIAL_BACKOFF",
+            &mut webhooks_config.initial_backoff,
+            errors,
+        );
+        override_field(
+            "WEBHOOKS_MAX_BACKOFF",
+            &mut webhooks_config.max_backoff,
+            errors,
+        );
+        override_field("WEBHOOKS_TIMEOUT", &mut webhooks_config.timeout, errors);
+        override_field("TLS_ENABLED", &mut tls_config.enabled, errors);
+        override_field("TLS_HOST", &mut tls_config.host, errors);
+        override_field("TLS_PORT", &mut tls_config.port, errors);
+        override_field("TLS_CERT_PATH", &mut tls_config.cert_path, errors);
+        override_field("TLS_KEY_PATH", &mut tls_config.key_path, errors);
+        override_field("TLS_HTTP", &mut tls_config.http, errors);
+        override_field("ADMIN_TOKEN", &mut admin_config.token, errors);
+    }
+
+    fn validate(&self, part: Part, errors: &mut Vec<String>) {
+        let file = paths::GENERAL_CONFIG;
+
+        if self.logger_config.dirpath.is_empty() {
+            errors.push(format!("{}: logger dirpath must not be empty", file));
+        }
+        if self.metrics_collector_config.printer_period == 0 {
+            errors.push(format!("{}: metrics printer_period must be positive", file));
+        }
+        match part {
+            Part::One => self.request_handler_config.validate(file, errors),
+            Part::Two => self.validate_part2(file, errors),
+        }
+    }
+
+    fn validate_part2(&self, file: &str, errors: &mut Vec<String>) {
+        let StatusServiceConfig {
+            eviction_period,
+            max_entries,
+            archive_config,
+            store_config,
+            ..
+        } = &self.status_service_config;
+
+        if *eviction_period == 0 {
+            errors.push(format!("{}: status eviction_period must be positive", file));
+        }
+        if *max_entries == 0 {
+            errors.push(format!("{}: status max_entries must be positive", file));
+        }
+        if archive_config.dirpath.is_empty() {
+            errors.push(format!("{}: archive dirpath must not be empty", file));
+        }
+        if archive_config.max_file_size == 0 {
+            errors.push(format!("{}: archive max_file_size must be positive", file));
+        }
+        if archive_config.max_files == 0 {
+            errors.push(format!("{}: archive max_files must be positive", file));
+        }
+        if store_config.backend == StoreBackend::Sqlite && store_config.path.is_empty() {
+            errors.push(format!("{}: sqlite store path must not be empty", file));
+        }
+        self.offers_config.validate(file, errors);
+        self.webhooks_config.validate(file, errors);
+        self.tls_config.validate(file, self.port, errors);
+    }
+}
+
+impl FromStr for HttpMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "serve" => Ok(HttpMode::Serve),
+            "redirect" => Ok(HttpMode::Redirect),
+            "disabled" => Ok(HttpMode::Disabled),
+            _ => Err(String::from("expected serve, redirect or disabled")),
+        }
+    }
+}
+
+impl TlsConfig {
+    fn validate(&self, file: &str, http_port: u16, errors: &mut Vec<String>) {
+        if !self.enabled {
+            if self.http != HttpMode::Serve {
+                errors.push(format!(
+                    "{}: tls must be enabled to redirect or disable plain HTTP",
+                    file
+                ));
+            }
+            return;
+        }
+        if self.port == 0 {
+            errors.push(format!("{}: tls port must be positive", file));
+        }
+        if self.port == http_port && self.http != HttpMode::Disabled {
+            errors.push(format!("{}: tls port must differ from the HTTP port", file));
+        }
+        if self.http == HttpMode::Redirect && self.host.is_empty() {
+            errors.push(format!(
+                "{}: tls host must not be empty to redirect plain HTTP",
+                file
+            ));
+        }
+        if self.cert_path.is_empty() {
+            errors.push(format!("{}: tls cert_path must not be empty", file));
+        }
+        if self.key_path.is_empty() {
+            errors.push(format!("{}: tls key_path must not be empty", file));
+        }
+    }
+}
+
+impl FromStr for StoreBackend {
+    type Err = String;
+
+    fn from_str(backend: &str) -> Result<Self, Self::Err> {
+        match backend {
+            "memory" => Ok(StoreBackend::Memory),
+            "sqlite" => Ok(StoreBackend::Sqlite),
+            _ => Err(String::from("expected memory or sqlite")),
+        }
+    }
+}
+
+impl RequestHandlerConfig {
+    fn validate(&self, file: &str, errors: &mut Vec<String>) {
+        if self.pool_size == 0 {
+            errors.push(format!(
+                "{}: request handler pool_size must be positive",
+                file
+            ));
+        }
+        if self.queue_size == 0 {
+            errors.push(format!(
+                "{}: request handler queue_size must be positive",
+                file
+            ));
+        }
+    }
+}
+
+impl WebhooksConfig {
+    fn validate(&self, file: &str, errors: &mut Vec<String>) {
+        if self.max_attempts == 0 {
+            errors.push(format!("{}: webhooks max_attempts must be positive", file));
+        }
+        if self.initial_backoff == 0 {
+            errors.push(format!(
+                "{}: webhooks initial_backoff must be positive",
+                file
+            ));
+        }
+        if self.max_backoff < self.initial_backoff {
+            errors.push(format!(
+                "{}: webhooks max_backoff must not be lower than initial_backoff",
+                file
+            ));
+        }
+        if self.timeout == 0 {
+            errors.push(format!("{}: webhooks timeout must be positive", file));
+        }
+    }
+
+    /// Returns whether webhooks are delivered (a secret to sign them is configured).
+
+    pub fn enabled(&self) -> bool {
+        !self.secret.is_empty()
+    }
+
+    /// Time to wait (ms) before retrying after the given failed attempt (starting at 1).
+
+    pub fn backoff(&self, attempt: u32) -> u64 {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+impl OffersConfig {
+    fn validate(&self, file: &str, errors: &mut Vec<String>) {
+        if self.refresh_period == 0 {
+            errors.push(format!("{}: offers refresh_period must be positive", file));
+        }
+        if self.validity == 0 {
+            errors.push(format!("{}: offers validity must be positive", file));
+        }
+
+        let mut previous_top = 0;
+        for DiscountTier { top, discount } in &self.tiers {
+            if *top <= previous_top {
+                errors.push(format!(
+                    "{}: offers tiers must have increasing positive tops (got {} after {})",
+                    file, top, previous_top
+                ));
+            }
+            if !(1..=100).contains(discount) {
+                errors.push(format!(
+                    "{}: offers tier discount must be between 1 and 100 (got {})",
+                    file, discount
+                ));
+            }
+            previous_top = *top;
+        }
+    }
+
+    /// Returns the discount for the route ranked in the given (1-based) position, if any.
+
+    pub fn discount_for(&self, rank: usize) -> Option<u8> {
+        self.tiers
+            .iter()
+            .find(|tier| rank <= tier.top)
+            .map(|tier| tier.discount)
+    }
+
+    /// Returns how many of the most booked routes get an offer.
+
+    pub fn n_routes(&self) -> usize {
+        self.tiers.last().map_or(0, |tier| tier.top)
+    }
+}
+
+impl WebServiceConfig {
+    fn apply_overrides(&mut self, prefix: &str, errors: &mut Vec<String>) {
+        override_field(
+            &format!("{}_RATE_LIMIT", prefix),
+            &mut self.rate_limit,
+            errors,
+        );
+        override_field(
+            &format!("{}_FAILURE_RATE", prefix),
+            &mut self.failure_rate,
+            errors,
+        );
+        override_field(
+            &format!("{}_RETRY_TIME", prefix),
+            &mut self.retry_time,
+            errors,
+        );
+        override_field(
+            &format!("{}_MIN_DELAY", prefix),
+            &mut self.min_delay,
+            errors,
+        );
+        override_field(
+            &format!("{}_MAX_DELAY", prefix),
+            &mut self.max_delay,
+            errors,
+        );
+        override_field(&format!("{}_NAME", prefix), &mut self.name, errors);
+    }
+
+    fn validate(&self, file: &str, errors: &mut Vec<String>) {
+        let name = &self.name;
+
+        if name.is_empty() {
+            errors.push(format!("{}: webservice name must not be empty", file));
+        }
+        if self.rate_limit <= 0 {
+            errors.push(format!(
+                "{}: [{}] rate_limit must be positive (got {})",
+                file, name, self.rate_limit
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.failure_rate) {
+            errors.push(format!(
+                "{}: [{}] failure_rate must be between 0 and 1 (got {})",
+                file, name, self.failure_rate
+            ));
+        }
+        if self.min_delay >= self.max_delay {
+            errors.push(format!(
+                "{}: [{}] min_delay must be lower than max_delay (got {} >= {})",
+                file, name, self.min_delay, self.max_delay
+            ));
+        }
+
+        let mut routes = HashSet::new();
+        for flight in self.flights.iter().flatten() {
+            if flight.origin.is_empty() || flight.destiny.is_empty() {
+                errors.push(format!(
+                    "{}: [{}] flight origin and destiny must not be empty",
+                    file, name
+                ));
+            } else if flight.origin == flight.destiny {
+                errors.push(format!(
+                    "{}: [{}] flight {} -> {} must have different origin and destiny",
+                    file, name, flight.origin, flight.destiny
+                ));
+            }
+            if !routes.insert((&flight.origin, &flight.destiny)) {
+                errors.push(format!(
+                    "{}: [{}] duplicated flight {} -> {}",
+                    file, name, flight.origin, flight.destiny
+                ));
+            }
+        }
+    }
+}
+
+fn validate_airlines(airlines: &[AirlineConfig], errors: &mut Vec<String>) {
+    let file = paths::AIRLINES_CONFIG;
+    let mut names = HashSet::new();
+
+    if airlines.is_empty() {
+        errors.push(format!("{}: at least one airline is required", file));
+    }
+    for airline in airlines {
+        airline.validate(file, errors);
+        if !names.insert(&airline.name) {
+            errors.push(format!(
+                "{}: [{}] duplicated airline name",
+                file, airline.name
+            ));
+        }
+    }
+}
+
+fn read_file<T: DeserializeOwned>(
+    config_dir: &str,
+    filename: &str,
+    errors: &mut Vec<String>,
+) -> Option<T> {
+    let path = paths::config_path(config_dir, filename);
+    let data = match std::fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(err) => {
+            errors.push(format!("{}: could not be read ({})", path, err));
+            return None;
+        }
+    };
+
+    match serde_json::from_str(&data) {
+        Ok(config) => Some(config),
+        Err(err) => {
+            errors.push(format!("{}: invalid format ({})", path, err));
+            None
+        }
+    }
+}
+
+fn override_field<T: FromStr>(key: &str, field: &mut T, errors: &mut Vec<String>)
+where
+    T::Err: fmt::Display,
+{
+    if let Ok(value) = env::var(key) {
+        match value.parse::<T>() {
+            Ok(parsed) => *field = parsed,
+            Err(err) => errors.push(format!(
+                "env: {} has an invalid value '{}' ({})",
+                key, value, err
+            )),
+        }
+    }
+}
+
+fn override_optional_field<T: FromStr>(key: &str, field: &mut Option<T>, errors: &mut Vec<String>)
+where
+    T::Err: fmt::Display,
+{
+    if let Ok(value) = env::var(key) {
+        match value.parse::<T>() {
+            Ok(parsed) => *field = Some(parsed),
+            Err(err) => errors.push(format!(
+                "env: {} has an invalid value '{}' ({})",
+                key, value, err
+            )),
+        }
+    }
+}
+
+/// Given a name this function returns it as an environment variable key fragment.
+
+pub fn env_key(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect()
+}
+
+// DEFAULTS -------------------------------------------------------------------
+
+impl Default for StatusServiceConfig {
+    fn default() -> Self {
+        StatusServiceConfig {
+            retention_ttl: 300000,
+            max_entries: 10000,
+            eviction_period: 10000,
+            archive_config: ArchiveConfig {
+                dirpath: String::from("./logs/archive"),
+                max_file_size: 10 * 1024 * 1024,
+                max_files: 5,
+            },
+            store_config: StoreConfig::default(),
+        }
+    }
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        StoreConfig {
+            backend: StoreBackend::Memory,
+            path: String::from("./logs/status.db"),
+        }
+    }
+}
+
+impl Default for RequestHandlerConfig {
+    fn default() -> Self {
+        RequestHandlerConfig {
+            pool_size: 32,
+            queue_size: 64,
+        }
+    }
+}
+
+impl Default for OffersConfig {
+    fn default() -> Self {
+        OffersConfig {
+            refresh_period: 30000,
+            validity: 120000,
+            tiers: vec![
+                DiscountTier {
+                    top: 1,
+                    discount: 30,
+                },
+                DiscountTier {
+                    top: 3,
+                    discount: 20,
+                },
+                DiscountTier {
+                    top: 5,
+                    discount: 10,
+                },
+            ],
+        }
+    }
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        WebhooksConfig {
+            secret: String::new(),
+            max_attempts: 5,
+            initial_backoff: 1000,
+            max_backoff: 30000,
+            timeout: 5000,
+        }
+    }
+}
+
+// ERRORS ---------------------------------------------------------------------
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[CRITICAL] Invalid configuration ({} error(s) found):",
+            self.errors.len()
+        )?;
+        for error in &self.errors {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process, sync::Mutex};
+
+    use super::*;
+    use crate::common::utils::uuid;
+
+    /// Overrides are read from the environment, shared by every test of the process.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// General config with only its required sections, as before part2 grew its own.
+    const GENERAL: &str = r#"{
+        "port": 8080,
+        "logger_config": { "dirpath": "./logs" },
+        "metrics_collector_config": { "printer_period": 5000, "n_most_booked": 10 }
+    }"#;
+
+    const AIRLINES: &str = r#"[
+        { "name": "Iberia", "rate_limit": 2, "failure_rate": 0.1, "retry_time": 1, "min_delay": 0, "max_delay": 1 }
+    ]"#;
+
+    const HOTEL: &str = r#"{
+        "name": "Hotel", "rate_limit": 2, "failure_rate": 0.1, "retry_time": 1, "min_delay": 0, "max_delay": 1
+    }"#;
+
+    fn config_dir(general: &str, airlines: &str, hotel: &str) -> String {
+        let dirpath = env::temp_dir()
+            .join(format!("tp1-config-{}-{}", process::id(), uuid()))
+            .to_string_lossy()
+            .to_string();
+        fs::create_dir_all(&dirpath).expect("Could not create temp dir");
+        for (filename, data) in [
+            (paths::GENERAL_CONFIG, general),
+            (paths::AIRLINES_CONFIG, airlines),
+            (paths::HOTEL_CONFIG, hotel),
+        ] {
+            fs::write(paths::config_path(&dirpath, filename), data)
+                .expect("Could not write config");
+        }
+        dirpath
+    }
+
+    fn load(config_dir: &str, part: Part) -> Result<Config, Vec<String>> {
+        let _env = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        Config::load(config_dir, part).map_err(|err| err.errors)
+    }
+
+    /// General config with the given sections added.
+    fn general_with(sections: &str) -> String {
+        format!(
+            "{}, {} }}",
+            GENERAL.trim_end().trim_end_matches('}'),
+            sections
+        )
+    }
+
+    #[test]
+    fn loads_configs_without_optional_sections() {
+        let dirpath = config_dir(GENERAL, AIRLINES, HOTEL);
+
+        for part in [Part::One, Part::Two] {
+            let config = load(&dirpath, part).expect("Config not loaded");
+            assert_eq!(config.general.request_handler_config.pool_size, 32);
+            assert_eq!(
+                config.general.status_service_config.store_config.backend,
+                StoreBackend::Memory
+            );
+            assert!(!config.general.webhooks_config.enabled());
+            assert!(config.general.admin_config.token.is_empty());
+        }
+        fs::remove_dir_all(dirpath).ok();
+    }
+
+    #[test]
+    fn validates_part2_sections_only_in_part2() {
+        let general = general_with(
+            r#""request_handler_config": { "pool_size": 0, "queue_size": 1 },
+            "offers_config": { "refresh_period": 0, "validity": 1, "tiers": [] },
+            "webhooks_config": { "secret": "s", "max_attempts": 0, "initial_backoff": 1, "max_backoff": 1, "timeout": 1 }"#,
+        );
+        let dirpath = config_dir(&general, AIRLINES, HOTEL);
+
+        let part1_errors = load(&dirpath, Part::One).expect_err("Config loaded");
+        assert_eq!(part1_errors.len(), 1, "{:?}", part1_errors);
+        assert!(part1_errors[0].contains("pool_size"));
+
+        let part2_errors = load(&dirpath, Part::Two).expect_err("Config loaded");
+        assert_eq!(part2_errors.len(), 2, "{:?}", part2_errors);
+        assert!(part2_errors[0].contains("refresh_period"));
+        assert!(part2_errors[1].contains("max_attempts"));
+        fs::remove_dir_all(dirpath).ok();
+    }
+
+    #[test]
+    fn reports_every_validation_error() {
+        let airlines = r#"[
+            { "name": "Iberia", "rate_limit": 0, "failure_rate": 0.1, "retry_time": 1, "min_delay": 0, "max_delay": 1 },
+            { "name": "Iberia", "rate_limit": 1, "failure_rate": 2.0, "retry_time": 1, "min_delay": 2, "max_delay": 1 }
+        ]"#;
+        let hotel = r#"{
+            "name": "Hotel", "rate_limit": 1, "failure_rate": 0.1, "retry_time": 1, "min_delay": 0, "max_delay": 1,
+            "flights": []
+        }"#;
+        let dirpath = config_dir(GENERAL, airlines, hotel);
+
+        // rate_limit, failure_rate, min_delay, duplicated name and hotel flights
+        let errors = load(&dirpath, Part::One).expect_err("Config loaded");
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        fs::remove_dir_all(dirpath).ok();
+    }
+
+    #[test]
+    fn applies_env_overrides() {
+        let dirpath = config_dir(GENERAL, AIRLINES, HOTEL);
+        let overrides = [
+            ("PORT", "9000"),
+            ("SEED", "3"),
+            ("AIRLINE_IBERIA_FAILURE_RATE", "0.5"),
+            ("HOTEL_RATE_LIMIT", "7"),
+            ("WEBHOOKS_SECRET", "secret"),
+            ("STORE_BACKEND", "sqlite"),
+        ];
+
+        let result = {
+            let _env = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+            for (key, value) in overrides {
+                env::set_var(key, value);
+            }
+            let loaded = Config::load(&dirpath, Part::Two);
+            env::set_var("METRICS_PRINTER_PERIOD", "soon");
+            let invalid = Config::load(&dirpath, Part::Two);
+            for (key, _) in overrides {
+                env::remove_var(key);
+            }
+            env::remove_var("METRICS_PRINTER_PERIOD");
+            (loaded, invalid)
+        };
+
+        let config = result.0.expect("Config not loaded");
+        assert_eq!(config.general.port, 9000);
+        assert_eq!(config.general.seed, Some(3));
+        assert_eq!(config.airlines[0].failure_rate, 0.5);
+        assert_eq!(config.hotel.rate_limit, 7);
+        assert!(config.general.webhooks_config.enabled());
+        assert_eq!(
+            config.general.status_service_config.store_config.backend,
+            StoreBackend::Sqlite
+        );
 
-        Ok(config)
+        let errors = result.1.expect_err("Config loaded").errors;
+        assert_eq!(
+            errors,
+            vec![String::from(
+                "env: METRICS_PRINTER_PERIOD has an invalid value 'soon' (invalid digit found in string)"
+            )]
+        );
+        fs::remove_dir_all(dirpath).ok();
     }
 }
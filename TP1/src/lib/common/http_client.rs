@@ -0,0 +1,505 @@
+//! Minimal blocking HTTP/1.1 client.
+//!
+//! Supports `http://` and `https://` URLs (with rustls, trusting the Mozilla root
+//! certificates) and one request per connection (`Connection: close`), which is
+//! enough to talk to our own API and post webhooks.
+
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Write},
+    net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use rustls::{
+    ClientConfig, ClientConnection, OwnedTrustAnchor, RootCertStore, ServerName, StreamOwned,
+};
+
+// TYPES ----------------------------------------------------------------------
+
+/// Response status code and body.
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+/// Parsed `http[s]://host[:port][/path]` URL (`tls` for `https`).
+#[derive(Clone, Debug)]
+pub struct Url {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+// FUNCTIONS ------------------------------------------------------------------
+
+impl Url {
+    /// Given a `http[s]://host[:port][/path]` string this method returns its parts.
+
+    pub fn parse(url: &str) -> io::Result<Url> {
+        // They would end up in the request line or in the Host header
+        if url.chars().any(is_unsafe) {
+            return Err(invalid_input(format!(
+                "Invalid character in URL: {:?}",
+                url
+            )));
+        }
+        let (tls, rest) = match (url.strip_prefix("http://"), url.strip_prefix("https://")) {
+            (Some(rest), _) => (false, rest),
+            (_, Some(rest)) => (true, rest),
+            _ => {
+                return Err(invalid_input(format!(
+                    "Unsupported URL (only http:// or https://): {}",
+                    url
+                )))
+            }
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse::<u16>()
+                    .map_err(|_| invalid_input(format!("Invalid port in URL: {}", url)))?,
+            ),
+            None => (authority, if tls { 443 } else { 80 }),
+        };
+        if host.is_empty() {
+            return Err(invalid_input(format!("Missing host in URL: {}", url)));
+        }
+
+        Ok(Url {
+            tls,
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Returns true if the host is `localhost` or a non public IP address, without
+    /// resolving names.
+
+    pub fn has_internal_host(&self) -> bool {
+        let host = self.host.trim_start_matches('[').trim_end_matches(']');
+        match host.parse::<IpAddr>() {
+            Ok(ip) => is_internal(ip),
+            Err(_) => {
+                let host = host.to_ascii_lowercase();
+                host == "localhost" || host.ends_with(".localhost")
+            }
+        }
+    }
+
+    /// Returns a new URL with the same host and port and the given path.
+
+    pub fn with_path(&self, path: &str) -> Url {
+        Url {
+            tls: self.tls,
+            host: self.host.clone(),
+            port: self.port,
+            path: path.to_string(),
+        }
+    }
+}
+
+/// Sends a request and waits for the whole response.
+
+pub fn request(
+    method: &str,
+    url: &Url,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+    timeout: Duration,
+) -> io::Result<HttpResponse> {
+    let addr = resolve(url)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| invalid_input(format!("Could not resolve host {}", url.host)))?;
+
+    send(addr, method, url, headers, body, timeout)
+}
+
+/// Like `request`, but refuses hosts that resolve to loopback, private or link-local
+/// addresses, for URLs given by users.
+
+pub fn request_public(
+    method: &str,
+    url: &Url,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+    timeout: Duration,
+) -> io::Result<HttpResponse> {
+    let addrs = resolve(url)?;
+    // The checked address is the one connected to, so the name can't be resolved again
+    let addr = match addrs.iter().find(|addr| is_internal(addr.ip())) {
+        Some(addr) => {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "Host {} resolves to internal address {}",
+                    url.host,
+                    addr.ip()
+                ),
+            ))
+        }
+        None => addrs
+            .into_iter()
+            .next()
+            .ok_or_else(|| invalid_input(format!("Could not resolve host {}", url.host)))?,
+    };
+
+    send(addr, method, url, headers, body, timeout)
+}
+
+/// Returns true for loopback, private, link-local and unspecified addresses.
+
+pub fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_internal(IpAddr::V4(ip)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+fn resolve(url: &Url) -> io::Result<Vec<SocketAddr>> {
+    let host = url.host.trim_start_matches('[').trim_end_matches(']');
+    Ok((host, url.port).to_socket_addrs()?.collect())
+}
+
+/// Writes the request to the given address and waits for the whole response.
+
+fn send(
+    addr: SocketAddr,
+    method: &str,
+    url: &Url,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+    timeout: Duration,
+) -> io::Result<HttpResponse> {
+    // Fields can be set without `Url::parse`, so the request can't be split here either
+    let request_line = [method, &url.host, &url.path];
+    let header_names = headers.iter().map(|(name, _value)| *name);
+    let unsafe_line = request_line
+        .iter()
+        .copied()
+        .chain(header_names)
+        .any(|part| part.is_empty() || part.chars().any(is_unsafe));
+    let unsafe_value = headers
+        .iter()
+        .any(|(_name, value)| value.chars().any(char::is_control));
+    if unsafe_line || unsafe_value {
+        return Err(invalid_input(String::from(
+            "Invalid character in request line or headers",
+        )));
+    }
+
+    let stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let body = body.unwrap_or("");
+    let mut req = format!(
+        "{} {} HTTP/1.1\r\nHost: {}:{}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        method,
+        url.path,
+        url.host,
+        url.port,
+        body.len()
+    );
+    for (name, value) in headers {
+        req += &format!("{}: {}\r\n", name, value);
+    }
+    req += "\r\n";
+    req += body;
+
+    let raw = match url.tls {
+        true => {
+            let server_name = ServerName::try_from(url.host.as_str())
+                .map_err(|_| invalid_input(format!("Invalid TLS server name {}", url.host)))?;
+            let conn =
+                ClientConnection::new(tls_config(), server_name).map_err(io::Error::other)?;
+            exchange(StreamOwned::new(conn, stream), &req)?
+        }
+        false => exchange(stream, &req)?,
+    };
+
+    parse_response(&raw)
+}
+
+/// Client TLS configuration, built once.
+
+fn tls_config() -> Arc<ClientConfig> {
+    static TLS_CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+
+    TLS_CONFIG
+        .get_or_init(|| {
+            let mut roots = RootCertStore::empty();
+            roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            }));
+            Arc::new(
+                ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_root_certificates(roots)
+                    .with_no_client_auth(),
+            )
+        })
+        .clone()
+}
+
+/// Writes the request and reads the whole response, until the server closes the connection.
+
+fn exchange(mut stream: impl Read + Write, req: &str) -> io::Result<Vec<u8>> {
+    stream.write_all(req.as_bytes())?;
+    stream.flush()?;
+
+    let mut raw = Vec::new();
+    match stream.read_to_end(&mut raw) {
+        Ok(_) => Ok(raw),
+        // Some servers close TLS connections without a close_notify, once the response was sent
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof && !raw.is_empty() => Ok(raw),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn get(url: &Url, timeout: Duration) -> io::Result<HttpResponse> {
+    request("GET", url, &[], None, timeout)
+}
+
+pub fn post_json(url: &Url, body: &str, timeout: Duration) -> io::Result<HttpResponse> {
+    request(
+        "POST",
+        url,
+        &[("Content-Type", "application/json")],
+        Some(body),
+        timeout,
+    )
+}
+
+fn parse_response(raw: &[u8]) -> io::Result<HttpResponse> {
+    let header_end = raw
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| invalid_data("Incomplete HTTP response"))?;
+    let head = String::from_utf8_lossy(&raw[..header_end]);
+    let payload = &raw[header_end + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|status_line| status_line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| invalid_data("Invalid HTTP status line"))?;
+
+    let mut chunked = false;
+    let mut content_length = None;
+    for line in lines {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), value.trim()),
+            None => continue,
+        };
+        match name.as_str() {
+            "transfer-encoding" => chunked = value.to_ascii_lowercase().contains("chunked"),
+            "content-length" => {
+                content_length = Some(
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| invalid_data("Invalid Content-Length"))?,
+                )
+            }
+            _ => (),
+        }
+    }
+
+    // Chunked encoding takes precedence over Content-Length, as in RFC 7230
+    let body = match (chunked, content_length) {
+        (true, _) => decode_chunked(payload)?,
+        (false, Some(length)) => payload
+            .get(..length)
+            .ok_or_else(|| invalid_data("Truncated body"))?
+            .to_vec(),
+        (false, None) => payload.to_vec(),
+    };
+
+    Ok(HttpResponse {
+        status,
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+}
+
+fn decode_chunked(mut payload: &[u8]) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+
+    loop {
+        let line_end = payload
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(|| invalid_data("Invalid chunked body"))?;
+        let size_line = String::from_utf8_lossy(&payload[..line_end]);
+        let size_hex = size_line.split(';').next().unwrap_or("").trim();
+        let size =
+            usize::from_str_radix(size_hex, 16).map_err(|_| invalid_data("Invalid chunk size"))?;
+        payload = &payload[line_end + 2..];
+
+        if size == 0 {
+            return Ok(body);
+        }
+        if payload.len() < size {
+            return Err(invalid_data("Truncated chunk"));
+        }
+        body.extend_from_slice(&payload[..size]);
+        payload = payload.get(size + 2..).unwrap_or(&[]);
+    }
+}
+
+/// Whitespace and control characters (CR and LF among them) can't be part of URLs,
+/// methods or header names. Header values can have spaces, but no control characters.
+
+fn is_unsafe(c: char) -> bool {
+    c.is_whitespace() || c.is_control()
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_http_and_https_urls() {
+        let url = Url::parse("http://localhost:8080/request?id=1").expect("invalid URL");
+        assert!(!url.tls);
+        assert_eq!((url.host.as_str(), url.port), ("localhost", 8080));
+        assert_eq!(url.path, "/request?id=1");
+
+        let url = Url::parse("https://partner.example.com").expect("invalid URL");
+        assert!(url.tls);
+        assert_eq!((url.host.as_str(), url.port), ("partner.example.com", 443));
+        assert_eq!(url.path, "/");
+
+        assert!(Url::parse("ftp://partner.example.com").is_err());
+        assert!(Url::parse("https://:8443/").is_err());
+    }
+
+    #[test]
+    fn rejects_urls_that_would_split_the_request() {
+        for url in [
+            "http://partner.example.com/hook HTTP/1.1\r\nX-Injected: 1",
+            "http://partner.example.com/hook\nGET /admin",
+            "http://partner.example.com\r\n/hook",
+            "http://partner.example.com/with space",
+            "http://partner.example.com/\thook",
+            "http://partner.example.com/\u{0}hook",
+        ] {
+            assert!(Url::parse(url).is_err(), "{:?} accepted", url);
+        }
+
+        // Nor can they be smuggled in fields set by hand or in header values
+        let url = Url::parse("http://127.0.0.1:9/hook").expect("invalid URL");
+        let timeout = Duration::from_millis(100);
+        let split_path = url.with_path("/hook\r\nX-Injected: 1");
+        let err = request("GET", &split_path, &[], None, timeout).expect_err("request sent");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let split_header = [("X-Request-ID", "id\r\nX-Injected: 1")];
+        let err = request("GET", &url, &split_header, None, timeout).expect_err("request sent");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn detects_internal_hosts() {
+        for url in [
+            "http://localhost:8080/hook",
+            "http://api.localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://10.0.0.1/hook",
+            "http://172.16.5.4/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://0.0.0.0/hook",
+            "http://[::1]:8080/hook",
+            "http://[fe80::1]:8080/hook",
+            "http://[fd00::1]:8080/hook",
+            "http://[::ffff:127.0.0.1]:8080/hook",
+        ] {
+            let url = Url::parse(url).expect("invalid URL");
+            assert!(url.has_internal_host(), "{} is not internal", url.host);
+        }
+        for url in ["https://partner.example.com/hook", "http://8.8.8.8/hook"] {
+            let url = Url::parse(url).expect("invalid URL");
+            assert!(!url.has_internal_host(), "{} is internal", url.host);
+        }
+
+        // Resolved addresses are checked before connecting
+        let url = Url::parse("http://127.0.0.1:9/hook").expect("invalid URL");
+        let err = request_public("POST", &url, &[], Some("{}"), Duration::from_millis(100))
+            .expect_err("request sent");
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn parses_content_length_bodies() {
+        let res = parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello")
+            .expect("invalid response");
+        assert_eq!((res.status, res.body.as_str()), (200, "hello"));
+
+        // Anything past the declared length is not part of the body
+        let res = parse_response(b"HTTP/1.1 404 Not Found\r\ncontent-length: 3\r\n\r\nabcdef")
+            .expect("invalid response");
+        assert_eq!((res.status, res.body.as_str()), (404, "abc"));
+
+        // Without a length, the body lasts until the connection is closed
+        let res = parse_response(b"HTTP/1.1 200 OK\r\n\r\nuntil close").expect("invalid response");
+        assert_eq!(res.body, "until close");
+
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nshort").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: x\r\n\r\n").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n").is_err());
+    }
+
+    #[test]
+    fn parses_chunked_bodies() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            4\r\nWiki\r\n6;ext=1\r\npedia \r\nE\r\nin \r\n\r\nchunks.\r\n0\r\n\r\n";
+        let res = parse_response(raw).expect("invalid response");
+        assert_eq!(res.body, "Wikipedia in \r\n\r\nchunks.");
+
+        // Chunked encoding takes precedence over a (wrong) Content-Length
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nTransfer-Encoding: chunked\r\n\r\n\
+            3\r\nabc\r\n0\r\n\r\n";
+        assert_eq!(parse_response(raw).expect("invalid response").body, "abc");
+
+        let truncated = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\na\r\nabc";
+        assert!(parse_response(truncated).is_err());
+        let invalid_size = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\nabc";
+        assert!(parse_response(invalid_size).is_err());
+    }
+}
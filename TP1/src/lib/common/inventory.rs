@@ -0,0 +1,186 @@
+//! Airline seat inventory shared by both implementations.
+
+use std::{collections::BTreeMap, fmt};
+
+use serde::Serialize;
+
+use crate::common::{config::FlightConfig, request::Route};
+
+// TYPES ----------------------------------------------------------------------
+
+struct Flight {
+    seats: u32,
+    sold: u32,
+}
+
+/// Seats left in each flight of a webservice. A webservice without declared
+/// flights (e.g. the hotel) has unlimited seats for every route.
+pub struct SeatInventory {
+    flights: Option<BTreeMap<Route, Flight>>,
+}
+
+/// Remaining seats of a flight.
+#[derive(Clone, Serialize)]
+pub struct FlightInventory {
+    pub route: Route,
+    pub seats: u32,
+    pub remaining: u32,
+}
+
+/// Reason why a booking is definitively rejected (retrying will not help).
+#[derive(Clone, Debug)]
+pub enum BookingRejection {
+    SoldOut(Route),
+    NoFlight(Route),
+}
+
+// FUNCTIONS ------------------------------------------------------------------
+
+impl SeatInventory {
+    /// Given the declared flights (if any) this method returns the inventory with every seat available.
+
+    pub fn from_config(flights: &Option<Vec<FlightConfig>>) -> Self {
+        let flights = flights.as_ref().map(|flights| {
+            flights
+                .iter()
+                .map(|flight| {
+                    (
+                        Route {
+                            origin: flight.origin.clone(),
+                            destiny: flight.destiny.clone(),
+                        },
+                        Flight {
+                            seats: flight.seats,
+                            sold: 0,
+                        },
+                    )
+                })
+                .collect()
+        });
+
+        SeatInventory { flights }
+    }
+
+    /// Sells `seats` seats of the flight for the given route, all of them or none.
+
+    pub fn book(&mut self, route: &Route, seats: u32) -> Result<(), BookingRejection> {
+        let flights = match &mut self.flights {
+            Some(flights) => flights,
+            None => return Ok(()),
+        };
+
+        match flights.get_mut(route) {
+            Some(flight) if flight.sold + seats <= flight.seats => {
+                flight.sold += seats;
+                Ok(())
+            }
+            Some(_) => Err(BookingRejection::SoldOut(route.clone())),
+            None => Err(BookingRejection::NoFlight(route.clone())),
+        }
+    }
+
+    /// Gives back `seats` seats of the flight for the given route, previously sold with book
+    /// (e.g. because another booking of the same request was rejected).
+
+    pub fn release(&mut self, route: &Route, seats: u32) {
+        if let Some(flight) = self
+            .flights
+            .as_mut()
+            .and_then(|flights| flights.get_mut(route))
+        {
+            flight.sold = flight.sold.saturating_sub(seats);
+        }
+    }
+
+    /// Whether the seats are limited (flights were declared).
+
+    pub fn is_limited(&self) -> bool {
+        self.flights.is_some()
+    }
+
+    /// Returns the remaining seats of every flight, sorted by route.
+
+    pub fn remaining(&self) -> Vec<FlightInventory> {
+        self.flights
+            .iter()
+            .flatten()
+            .map(|(route, flight)| FlightInventory {
+                route: route.clone(),
+                seats: flight.seats,
+                remaining: flight.seats - flight.sold,
+            })
+            .collect()
+    }
+}
+
+/// Formats the remaining seats of an airline as a table, ready to be logged.
+
+pub fn format_inventory(airline: &str, flights: &[FlightInventory]) -> String {
+    let mut inventory_msg: String = format!(
+        "{:=^41}\n|{:^39}|\n{:=^41}\n|{:^9}|{:^9}|{:^9}|{:^9}|\n{:=^41}",
+        "", airline, "", "ORIGIN", "DESTINY", "SEATS", "LEFT", ""
+    );
+
+    for FlightInventory {
+        route: Route { origin, destiny },
+        seats,
+        remaining,
+    } in flights
+    {
+        inventory_msg += &format!(
+            "\n|{:^9}|{:^9}|{:^9}|{:^9}|",
+            origin, destiny, seats, remaining
+        );
+    }
+    inventory_msg += &format!("\n{:=^41}", "");
+
+    inventory_msg
+}
+
+// ERRORS ---------------------------------------------------------------------
+
+impl fmt::Display for BookingRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BookingRejection::SoldOut(Route { origin, destiny }) => {
+                write!(f, "flight {} -> {} sold out", origin, destiny)
+            }
+            BookingRejection::NoFlight(Route { origin, destiny }) => {
+                write!(f, "no flight {} -> {}", origin, destiny)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(origin: &str, destiny: &str) -> Route {
+        Route {
+            origin: String::from(origin),
+            destiny: String::from(destiny),
+        }
+    }
+
+    #[test]
+    fn released_seats_can_be_booked_again() {
+        let mut inventory = SeatInventory::from_config(&Some(vec![FlightConfig {
+            origin: String::from("EZE"),
+            destiny: String::from("MAD"),
+            seats: 3,
+        }]));
+        let eze_mad = route("EZE", "MAD");
+
+        assert!(inventory.book(&eze_mad, 2).is_ok());
+        assert!(inventory.book(&eze_mad, 2).is_err());
+        inventory.release(&eze_mad, 2);
+        assert_eq!(inventory.remaining()[0].remaining, 3);
+        assert!(inventory.book(&eze_mad, 3).is_ok());
+
+        // Releasing an unknown flight (or more than sold) never underflows
+        inventory.release(&route("EZE", "BCN"), 1);
+        inventory.release(&eze_mad, 10);
+        assert_eq!(inventory.remaining()[0].remaining, 3);
+    }
+}
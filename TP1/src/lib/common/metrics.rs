@@ -0,0 +1,202 @@
+//! Metrics aggregation shared by both implementations.
+//!
+//! Metrics collected:
+//! * Mean request time
+//! * Top n routes
+//! * Number of requests
+//! * Number of passengers
+//! * Number of unfinished requests
+//! * Number of rejected requests
+//! * Operational metrics of each webservice
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::common::request::Route;
+
+// TYPES ----------------------------------------------------------------------
+
+/// Struct that is used to output top booked airline metrics.
+#[derive(Clone, Serialize)]
+pub struct RouteMetrics {
+    pub route: Route,
+    pub amount: u64,
+}
+
+pub type MostBookedRoutes = Vec<RouteMetrics>;
+
+/// Operational metrics of a webservice: bookings waiting for a free slot (`queued`),
+/// fetches in progress (`in_flight`), failed bookings waiting to be retried (`retrying`),
+/// finished fetches (`attempts`), the ones that failed and were retried (`failures`)
+/// and the total time waited before retrying (ms).
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct WebServiceMetrics {
+    pub queued: u64,
+    pub in_flight: u64,
+    pub retrying: u64,
+    pub attempts: u64,
+    pub failures: u64,
+    pub retry_wait_ms: u64,
+}
+
+/// Operational metrics of each webservice, sorted by name.
+pub type WebServicesMetrics = BTreeMap<String, WebServiceMetrics>;
+
+/// Aggregated metrics for the finished requests.
+#[derive(Default)]
+pub struct Metrics {
+    routes_booking_count: HashMap<Route, u64>,
+    reqs_duration_cumsum: i64,
+    n_reqs: u64,
+    n_passengers: u64,
+    n_unfinished: u64,
+    n_rejected: u64,
+}
+
+// FUNCTIONS ------------------------------------------------------------------
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// Registers a finished request that booked the given routes for `passengers`
+    /// passengers and took `duration_ms`.
+
+    pub fn add_request(&mut self, routes: Vec<Route>, passengers: u32, duration_ms: i64) {
+        self.n_reqs += 1;
+        self.n_passengers += passengers as u64;
+        self.reqs_duration_cumsum += duration_ms;
+        for route in routes {
+            *self.routes_booking_count.entry(route).or_insert(0) += 1;
+        }
+    }
+
+    /// Registers a request that was dropped before finishing (e.g. cancelled on shutdown).
+
+    pub fn add_unfinished(&mut self) {
+        self.n_unfinished += 1;
+    }
+
+    /// Registers a request that was definitively rejected (e.g. its flight was sold out).
+
+    pub fn add_rejected(&mut self) {
+        self.n_rejected += 1;
+    }
+
+    pub fn n_reqs(&self) -> u64 {
+        self.n_reqs
+    }
+
+    pub fn n_passengers(&self) -> u64 {
+        self.n_passengers
+    }
+
+    pub fn n_unfinished(&self) -> u64 {
+        self.n_unfinished
+    }
+
+    pub fn n_rejected(&self) -> u64 {
+        self.n_rejected
+    }
+
+    /// Mean time (in ms) to finish a request, 0 if none finished yet.
+
+    pub fn mean_time(&self) -> i64 {
+        match self.n_reqs {
+            0 => 0,
+            n_reqs => self.reqs_duration_cumsum / (n_reqs as i64),
+        }
+    }
+
+    /// Returns the `n` most booked routes, ties broken by route.
+
+    pub fn most_booked_routes(&self, n: usize) -> MostBookedRoutes {
+        let mut routes_booking_count_vec: Vec<(&Route, &u64)> =
+            self.routes_booking_count.iter().collect();
+        routes_booking_count_vec.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        routes_booking_count_vec.truncate(n);
+
+        routes_booking_count_vec
+            .into_iter()
+            .map(|(route, amount)| RouteMetrics {
+                route: route.clone(),
+                amount: *amount,
+            })
+            .collect()
+    }
+}
+
+/// Formats the most booked routes as a table, ready to be logged.
+
+pub fn format_most_booked_routes(most_booked_routes: &[RouteMetrics]) -> String {
+    let mut most_booked_routes_msg: String = format!(
+        "{:=^36}\n|{:^4}|{:^9}|{:^9}|{:^9}|\n{:=^36}",
+        "", "Nº", "ORIGIN", "DESTINY", "#", ""
+    );
+
+    for (
+        i,
+        RouteMetrics {
+            route: Route { origin, destiny },
+            amount,
+        },
+    ) in most_booked_routes.iter().enumerate()
+    {
+        most_booked_routes_msg += &format!(
+            "\n|{:^4}|{:^9}|{:^9}|{:^9}|",
+            i + 1,
+            origin,
+            destiny,
+            amount
+        );
+    }
+    most_booked_routes_msg += &format!("\n{:=^36}", "");
+
+    most_booked_routes_msg
+}
+
+/// Formats the webservices operational metrics as a table, ready to be logged.
+
+pub fn format_webservices_metrics(webservices: &WebServicesMetrics) -> String {
+    let mut webservices_msg: String = format!(
+        "{:=^91}\n|{:^27}|{:^9}|{:^9}|{:^9}|{:^9}|{:^9}|{:^11}|\n{:=^91}",
+        "",
+        "WEBSERVICE",
+        "QUEUED",
+        "IN FLIGHT",
+        "RETRYING",
+        "ATTEMPTS",
+        "FAILURES",
+        "RETRY WAIT",
+        ""
+    );
+
+    for (
+        name,
+        WebServiceMetrics {
+            queued,
+            in_flight,
+            retrying,
+            attempts,
+            failures,
+            retry_wait_ms,
+        },
+    ) in webservices
+    {
+        webservices_msg += &format!(
+            "\n|{:^27}|{:^9}|{:^9}|{:^9}|{:^9}|{:^9}|{:^11}|",
+            name,
+            queued,
+            in_flight,
+            retrying,
+            attempts,
+            failures,
+            format!("{} ms", retry_wait_ms)
+        );
+    }
+    webservices_msg += &format!("\n{:=^91}", "");
+
+    webservices_msg
+}
@@ -1,4 +1,9 @@
 // Public
 pub mod config;
+pub mod http_client;
+pub mod inventory;
+pub mod metrics;
 pub mod paths;
+pub mod request;
+pub mod simulation;
 pub mod utils;
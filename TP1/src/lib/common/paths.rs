@@ -1,7 +1,14 @@
 // Config files
-pub const AIRLINES_CONFIG: &str = "config/airlines.json";
-pub const HOTEL_CONFIG: &str = "config/hotel.json";
-pub const GENERAL_CONFIG: &str = "config/general.json";
+pub const DEFAULT_CONFIG_DIR: &str = "config";
+pub const AIRLINES_CONFIG: &str = "airlines.json";
+pub const HOTEL_CONFIG: &str = "hotel.json";
+pub const GENERAL_CONFIG: &str = "general.json";
 
 // Default requests
 pub const DEFAULT_REQUESTS: &str = "assets/requests.csv";
+
+/// Given a config directory and a config filename this function returns its path.
+
+pub fn config_path(config_dir: &str, filename: &str) -> String {
+    format!("{}/{}", config_dir.trim_end_matches('/'), filename)
+}
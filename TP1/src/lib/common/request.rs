@@ -0,0 +1,686 @@
+//! Request model shared by both implementations.
+
+use std::{collections::HashSet, convert::TryFrom, fmt};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// Separator between the stops of a csv row.
+const STOPS_SEPARATOR: char = ';';
+
+/// Separator between the airport and the airline of a stop in a csv row.
+const STOP_AIRLINE_SEPARATOR: char = ':';
+
+/// Separator between the passengers of a csv row.
+const PASSENGERS_SEPARATOR: char = ';';
+
+/// Separator between the name, document and type of a passenger in a csv row.
+const PASSENGER_FIELDS_SEPARATOR: char = ':';
+
+/// Incomming parsed request (a csv row in part 1, a JSON body in part 2).
+/// `origin` and `destiny` are the ends of the whole itinerary and `airline` flies its
+/// first leg. Multi-leg itineraries list their connections in `stops`.
+/// Round trips fly back on `return_date` through the same stops, on `return_airline`
+/// if given (or on the outbound airlines otherwise). Every flight books a seat for each
+/// of the `passengers` (a single anonymous seat if none is given). Bookings made through
+/// an offer (part 2 only) are tagged with its `offer_id`, and the final status of requests
+/// with a `callback_url` (part 2 only) is posted to it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RawRequest {
+    pub origin: String,
+    pub destiny: String,
+    pub airline: String,
+    pub package: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stops: Vec<Stop>,
+    #[serde(default)]
+    pub round_trip: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub departure_date: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub return_date: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub return_airline: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passengers: Vec<Passenger>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offer_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+}
+
+/// Passenger of a booking.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Passenger {
+    pub name: String,
+    pub document: String,
+    #[serde(rename = "type")]
+    pub passenger_type: PassengerType,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PassengerType {
+    Adult,
+    Child,
+}
+
+/// Connection of a multi-leg itinerary: the trip stops at `airport` and continues with `airline`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Stop {
+    pub airport: String,
+    pub airline: String,
+}
+
+/// Direction of a flight of an itinerary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trip {
+    Outbound,
+    Return,
+}
+
+/// Flight of an itinerary.
+#[derive(Clone, Debug)]
+pub struct Leg {
+    pub route: Route,
+    pub airline: String,
+    pub trip: Trip,
+}
+
+/// Hotel stay of a round trip package, from the departure to the return date.
+#[derive(Clone, Debug, Serialize)]
+pub struct Stay {
+    pub check_in: NaiveDate,
+    pub check_out: NaiveDate,
+    pub nights: i64,
+}
+
+/// Request as read from a csv row. The optional `stops` column lists the connections as
+/// `AIRPORT:Airline` pairs separated by `;` (e.g. `MAD:Iberia;FCO:Alitalia`). The optional
+/// `round_trip`, `departure_date`, `return_date` (`YYYY-MM-DD`) and `return_airline` columns
+/// may be left empty for one way trips. The optional `passengers` column lists them as
+/// `Name:Document:type` triples separated by `;` (e.g. `Ana Diaz:30111222:adult`).
+#[derive(Debug, Deserialize)]
+pub struct CsvRequest {
+    pub origin: String,
+    pub destiny: String,
+    pub airline: String,
+    pub package: bool,
+    #[serde(default)]
+    pub stops: String,
+    #[serde(default)]
+    pub round_trip: Option<bool>,
+    #[serde(default)]
+    pub departure_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub return_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub return_airline: Option<String>,
+    #[serde(default)]
+    pub passengers: String,
+}
+
+/// Struct that is used as hash index to keep track of metrics for that route.
+/// It has origin and destiny for request petition.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Route {
+    pub origin: String,
+    pub destiny: String,
+}
+
+impl RawRequest {
+    /// Returns the route of the whole itinerary.
+
+    pub fn route(&self) -> Route {
+        Route {
+            origin: self.origin.clone(),
+            destiny: self.destiny.clone(),
+        }
+    }
+
+    /// Returns the legs of the itinerary, in order: the outbound ones followed,
+    /// for round trips, by the return ones.
+
+    pub fn legs(&self) -> Vec<Leg> {
+        let mut legs = Vec::with_capacity(2 * (self.stops.len() + 1));
+        let mut origin = &self.origin;
+        let mut airline = &self.airline;
+
+        for stop in &self.stops {
+            legs.push(Leg {
+                route: Route {
+                    origin: origin.clone(),
+                    destiny: stop.airport.clone(),
+                },
+                airline: airline.clone(),
+                trip: Trip::Outbound,
+            });
+            origin = &stop.airport;
+            airline = &stop.airline;
+        }
+        legs.push(Leg {
+            route: Route {
+                origin: origin.clone(),
+                destiny: self.destiny.clone(),
+            },
+            airline: airline.clone(),
+            trip: Trip::Outbound,
+        });
+
+        if self.round_trip {
+            let return_legs: Vec<Leg> = legs
+                .iter()
+                .rev()
+                .map(|leg| Leg {
+                    route: leg.route.reversed(),
+                    airline: self
+                        .return_airline
+                        .clone()
+                        .unwrap_or_else(|| leg.airline.clone()),
+                    trip: Trip::Return,
+                })
+                .collect();
+            legs.extend(return_legs);
+        }
+
+        legs
+    }
+
+    /// Returns the routes booked by this request: each leg and, for multi-leg
+    /// itineraries, the whole itinerary (each way).
+
+    pub fn booked_routes(&self) -> Vec<Route> {
+        let mut routes: Vec<Route> = self.legs().into_iter().map(|leg| leg.route).collect();
+        if !self.stops.is_empty() {
+            routes.push(self.route());
+            if self.round_trip {
+                routes.push(self.route().reversed());
+            }
+        }
+        routes
+    }
+
+    /// Returns the number of seats each flight books.
+
+    pub fn party_size(&self) -> u32 {
+        self.passengers.len().max(1) as u32
+    }
+
+    /// Returns the hotel stay, spanning the dates between the outbound and the return
+    /// flights. Only round trip packages have one.
+
+    pub fn hotel_stay(&self) -> Option<Stay> {
+        match (
+            self.package && self.round_trip,
+            self.departure_date,
+            self.return_date,
+        ) {
+            (true, Some(check_in), Some(check_out)) => Some(Stay {
+                check_in,
+                check_out,
+                nights: (check_out - check_in).num_days(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Checks every leg flies between two different airports, and that round trips
+    /// (and only them) have a return date not before the departure one.
+
+    pub fn validate_itinerary(&self) -> Result<(), String> {
+        for Leg { route, .. } in self.legs() {
+            if route.origin.is_empty() || route.destiny.is_empty() {
+                return Err(String::from("itinerary airports must not be empty"));
+            }
+            if route.origin == route.destiny {
+                return Err(format!(
+                    "leg {} -> {} must have different origin and destiny",
+                    route.origin, route.destiny
+                ));
+            }
+        }
+
+        match (self.round_trip, self.departure_date, self.return_date) {
+            (false, _, Some(_)) => Err(String::from("one way trips must not have a return date")),
+            (false, _, None) if self.return_airline.is_some() => {
+                Err(String::from("one way trips must not have a return airline"))
+            }
+            (true, None, _) | (true, _, None) => Err(String::from(
+                "round trips must have a departure and a return date",
+            )),
+            (true, Some(departure), Some(back)) if back < departure => Err(format!(
+                "return date {} must not be before departure date {}",
+                back, departure
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks every passenger has a name and a document, and that no document is repeated.
+
+    pub fn validate_passengers(&self) -> Result<(), String> {
+        let mut documents = HashSet::new();
+        for Passenger { name, document, .. } in &self.passengers {
+            if name.trim().is_empty() || document.trim().is_empty() {
+                return Err(String::from("passengers must have a name and a document"));
+            }
+            if !documents.insert(document) {
+                return Err(format!("passenger document {} is repeated", document));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Route {
+    /// Returns the route flown the other way.
+
+    pub fn reversed(&self) -> Route {
+        Route {
+            origin: self.destiny.clone(),
+            destiny: self.origin.clone(),
+        }
+    }
+}
+
+/// Formats the passengers as in the csv `passengers` column.
+
+pub fn format_passengers(passengers: &[Passenger]) -> String {
+    passengers
+        .iter()
+        .map(|passenger| {
+            format!(
+                "{}{sep}{}{sep}{}",
+                passenger.name,
+                passenger.document,
+                passenger.passenger_type,
+                sep = PASSENGER_FIELDS_SEPARATOR
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(&PASSENGERS_SEPARATOR.to_string())
+}
+
+fn parse_passenger(passenger: &str) -> Result<Passenger, String> {
+    let fields: Vec<&str> = passenger
+        .split(PASSENGER_FIELDS_SEPARATOR)
+        .map(str::trim)
+        .collect();
+    let passenger_type = match fields.get(2) {
+        Some(&"adult") => Some(PassengerType::Adult),
+        Some(&"child") => Some(PassengerType::Child),
+        _ => None,
+    };
+
+    match (&fields[..], passenger_type) {
+        ([name, document, _], Some(passenger_type)) => Ok(Passenger {
+            name: name.to_string(),
+            document: document.to_string(),
+            passenger_type,
+        }),
+        _ => Err(format!(
+            "invalid passenger '{}' (expected Name{sep}Document{sep}adult|child)",
+            passenger,
+            sep = PASSENGER_FIELDS_SEPARATOR
+        )),
+    }
+}
+
+/// Formats the stops as in the csv `stops` column.
+
+pub fn format_stops(stops: &[Stop]) -> String {
+    stops
+        .iter()
+        .map(|stop| format!("{}{}{}", stop.airport, STOP_AIRLINE_SEPARATOR, stop.airline))
+        .collect::<Vec<_>>()
+        .join(&STOPS_SEPARATOR.to_string())
+}
+
+impl TryFrom<CsvRequest> for RawRequest {
+    type Error = String;
+
+    fn try_from(
+        CsvRequest {
+            origin,
+            destiny,
+            airline,
+            package,
+            stops,
+            round_trip,
+            departure_date,
+            return_date,
+            return_airline,
+            passengers,
+        }: CsvRequest,
+    ) -> Result<Self, Self::Error> {
+        let stops = stops
+            .split(STOPS_SEPARATOR)
+            .map(str::trim)
+            .filter(|stop| !stop.is_empty())
+            .map(|stop| match stop.split_once(STOP_AIRLINE_SEPARATOR) {
+                Some((airport, airline))
+                    if !airport.trim().is_empty() && !airline.trim().is_empty() =>
+                {
+                    Ok(Stop {
+                        airport: airport.trim().to_string(),
+                        airline: airline.trim().to_string(),
+                    })
+                }
+                _ => Err(format!(
+                    "invalid stop '{}' (expected AIRPORT{}Airline)",
+                    stop, STOP_AIRLINE_SEPARATOR
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let passengers = passengers
+            .split(PASSENGERS_SEPARATOR)
+            .map(str::trim)
+            .filter(|passenger| !passenger.is_empty())
+            .map(parse_passenger)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(RawRequest {
+            origin,
+            destiny,
+            airline,
+            package,
+            stops,
+            round_trip: round_trip.unwrap_or_default(),
+            departure_date,
+            return_date,
+            return_airline: return_airline.filter(|airline| !airline.trim().is_empty()),
+            passengers,
+            offer_id: None,
+            callback_url: None,
+        })
+    }
+}
+
+impl fmt::Display for PassengerType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PassengerType::Adult => write!(f, "adult"),
+            PassengerType::Child => write!(f, "child"),
+        }
+    }
+}
+
+impl fmt::Display for Trip {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trip::Outbound => write!(f, "outbound"),
+            Trip::Return => write!(f, "return"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{config::FlightConfig, inventory::SeatInventory};
+
+    const CSV_HEADER: &str = "origin,destiny,airline,package,stops";
+
+    const PASSENGERS_HEADER: &str = "origin,destiny,airline,package,passengers";
+
+    const ROUND_TRIP_HEADER: &str =
+        "origin,destiny,airline,package,stops,round_trip,departure_date,return_date,return_airline";
+
+    /// Parses a csv row under the given header, as the part 1 input is read.
+    fn parse_row(header: &str, row: &str) -> Result<RawRequest, String> {
+        let data = format!("{}\n{}\n", header, row);
+        let csv_request = csv::Reader::from_reader(data.as_bytes())
+            .deserialize::<CsvRequest>()
+            .next()
+            .expect("missing row")
+            .map_err(|err| err.to_string())?;
+        RawRequest::try_from(csv_request)
+    }
+
+    fn route(origin: &str, destiny: &str) -> Route {
+        Route {
+            origin: origin.to_string(),
+            destiny: destiny.to_string(),
+        }
+    }
+
+    /// Returns the legs of the request as `ORIGIN-DESTINY Airline trip` strings.
+    fn legs(req: &RawRequest) -> Vec<String> {
+        req.legs()
+            .into_iter()
+            .map(|leg| {
+                format!(
+                    "{}-{} {} {}",
+                    leg.route.origin, leg.route.destiny, leg.airline, leg.trip
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn legs_follow_the_stops_and_their_airlines() {
+        let req = parse_row(
+            CSV_HEADER,
+            "EZE,FCO,Aerolineas Argentinas,false,MAD:Iberia;CDG:Air France",
+        )
+        .expect("invalid row");
+
+        assert_eq!(
+            legs(&req),
+            vec![
+                "EZE-MAD Aerolineas Argentinas outbound",
+                "MAD-CDG Iberia outbound",
+                "CDG-FCO Air France outbound",
+            ]
+        );
+    }
+
+    #[test]
+    fn booked_routes_count_each_leg_and_the_whole_itinerary() {
+        let req = parse_row(
+            CSV_HEADER,
+            "EZE,FCO,Aerolineas Argentinas,false,MAD:Iberia;CDG:Air France",
+        )
+        .expect("invalid row");
+        assert_eq!(
+            req.booked_routes(),
+            vec![
+                route("EZE", "MAD"),
+                route("MAD", "CDG"),
+                route("CDG", "FCO"),
+                route("EZE", "FCO"),
+            ]
+        );
+
+        // Direct flights are only counted once
+        let req = parse_row(CSV_HEADER, "EZE,MAD,Iberia,false,").expect("invalid row");
+        assert_eq!(req.booked_routes(), vec![route("EZE", "MAD")]);
+    }
+
+    #[test]
+    fn rejects_malformed_stops() {
+        for stops in [
+            "MAD",
+            "MAD:Iberia;CDG",
+            ":Iberia",
+            "MAD:Iberia; :Air France",
+            "MAD:",
+        ] {
+            let row = format!("EZE,FCO,Iberia,false,{}", stops);
+            let err = parse_row(CSV_HEADER, &row).expect_err("stops accepted");
+            assert!(err.starts_with("invalid stop"), "{}: {}", stops, err);
+        }
+
+        // Airlines are checked by the request handlers, which know the configured ones
+        let req =
+            parse_row(CSV_HEADER, "EZE,FCO,Iberia,false,MAD:Unknown Air").expect("invalid row");
+        let airlines: Vec<String> = req.legs().into_iter().map(|leg| leg.airline).collect();
+        assert_eq!(airlines, vec!["Iberia", "Unknown Air"]);
+    }
+
+    #[test]
+    fn return_legs_fly_back_on_the_return_airline() {
+        let req = parse_row(
+            ROUND_TRIP_HEADER,
+            "EZE,CDG,Aerolineas Argentinas,false,MAD:Iberia,true,2024-03-01,2024-03-08,Air France",
+        )
+        .expect("invalid row");
+        req.validate_itinerary().expect("invalid itinerary");
+        assert_eq!(
+            legs(&req),
+            vec![
+                "EZE-MAD Aerolineas Argentinas outbound",
+                "MAD-CDG Iberia outbound",
+                "CDG-MAD Air France return",
+                "MAD-EZE Air France return",
+            ]
+        );
+
+        // Without a return airline, each leg flies back on its outbound airline
+        let req = parse_row(
+            ROUND_TRIP_HEADER,
+            "EZE,CDG,Aerolineas Argentinas,false,MAD:Iberia,true,2024-03-01,2024-03-08,",
+        )
+        .expect("invalid row");
+        assert_eq!(
+            legs(&req)[2..],
+            [
+                "CDG-MAD Iberia return",
+                "MAD-EZE Aerolineas Argentinas return"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_returns_before_departures() {
+        let req = parse_row(
+            ROUND_TRIP_HEADER,
+            "EZE,MAD,Iberia,false,,true,2024-03-08,2024-03-01,",
+        )
+        .expect("invalid row");
+        let err = req.validate_itinerary().expect_err("itinerary accepted");
+        assert!(err.contains("must not be before departure date"), "{}", err);
+
+        // Nor can round trips miss a date, or one way trips have a return
+        for row in [
+            "EZE,MAD,Iberia,false,,true,2024-03-01,,",
+            "EZE,MAD,Iberia,false,,false,2024-03-01,2024-03-08,",
+            "EZE,MAD,Iberia,false,,false,,,Iberia",
+        ] {
+            let req = parse_row(ROUND_TRIP_HEADER, row).expect("invalid row");
+            assert!(req.validate_itinerary().is_err(), "{} accepted", row);
+        }
+    }
+
+    #[test]
+    fn hotel_stay_covers_the_nights_between_the_flights() {
+        let req = parse_row(
+            ROUND_TRIP_HEADER,
+            "EZE,MAD,Iberia,true,,true,2024-02-26,2024-03-04,",
+        )
+        .expect("invalid row");
+        let stay = req.hotel_stay().expect("missing stay");
+        assert_eq!(stay.check_in, NaiveDate::from_ymd(2024, 2, 26));
+        assert_eq!(stay.check_out, NaiveDate::from_ymd(2024, 3, 4));
+        assert_eq!(stay.nights, 7);
+
+        // One way trips and round trips without the package have no stay
+        for row in [
+            "EZE,MAD,Iberia,true,,false,2024-02-26,,",
+            "EZE,MAD,Iberia,false,,true,2024-02-26,2024-03-04,",
+        ] {
+            let req = parse_row(ROUND_TRIP_HEADER, row).expect("invalid row");
+            assert!(req.hotel_stay().is_none(), "{} has a stay", row);
+        }
+    }
+
+    #[test]
+    fn parses_passenger_manifests() {
+        let req = parse_row(
+            PASSENGERS_HEADER,
+            "EZE,MAD,Iberia,false,Ana Diaz:30111222:adult; Leo Diaz : 45111222 : child",
+        )
+        .expect("invalid row");
+        req.validate_passengers().expect("invalid passengers");
+        let passengers: Vec<(&str, &str, PassengerType)> = req
+            .passengers
+            .iter()
+            .map(|p| (p.name.as_str(), p.document.as_str(), p.passenger_type))
+            .collect();
+        assert_eq!(
+            passengers,
+            vec![
+                ("Ana Diaz", "30111222", PassengerType::Adult),
+                ("Leo Diaz", "45111222", PassengerType::Child),
+            ]
+        );
+        assert_eq!(req.party_size(), 2);
+        assert_eq!(
+            format_passengers(&req.passengers),
+            "Ana Diaz:30111222:adult;Leo Diaz:45111222:child"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_passengers() {
+        for passengers in [
+            "Ana Diaz:30111222:senior",
+            "Ana Diaz:30111222:Adult",
+            "Ana Diaz:adult",
+            "Ana Diaz:30111222:adult:extra",
+            "Ana Diaz:30111222:adult;Leo Diaz",
+        ] {
+            let row = format!("EZE,MAD,Iberia,false,{}", passengers);
+            let err = parse_row(PASSENGERS_HEADER, &row).expect_err("passengers accepted");
+            assert!(
+                err.starts_with("invalid passenger"),
+                "{}: {}",
+                passengers,
+                err
+            );
+        }
+
+        // Empty names or documents, and repeated documents, fail the validation
+        for passengers in [
+            "Ana Diaz::adult",
+            ":30111222:adult",
+            "Ana Diaz:30111222:adult;Leo Diaz:30111222:child",
+        ] {
+            let row = format!("EZE,MAD,Iberia,false,{}", passengers);
+            let req = parse_row(PASSENGERS_HEADER, &row).expect("invalid row");
+            assert!(
+                req.validate_passengers().is_err(),
+                "{} accepted",
+                passengers
+            );
+        }
+    }
+
+    #[test]
+    fn party_size_is_the_seats_booked_per_flight() {
+        let mut inventory = SeatInventory::from_config(&Some(vec![FlightConfig {
+            origin: String::from("EZE"),
+            destiny: String::from("MAD"),
+            seats: 4,
+        }]));
+        let family = parse_row(
+            PASSENGERS_HEADER,
+            "EZE,MAD,Iberia,false,Ana:1:adult;Leo:2:child;Eva:3:child",
+        )
+        .expect("invalid row");
+        // Without passengers a single anonymous seat is booked
+        let anonymous = parse_row(PASSENGERS_HEADER, "EZE,MAD,Iberia,false,").expect("invalid row");
+        assert!(anonymous.passengers.is_empty());
+        assert_eq!((family.party_size(), anonymous.party_size()), (3, 1));
+
+        let route = family.route();
+        inventory
+            .book(&route, family.party_size())
+            .expect("family not booked");
+        assert!(inventory.book(&route, family.party_size()).is_err());
+        inventory
+            .book(&route, anonymous.party_size())
+            .expect("anonymous not booked");
+        assert_eq!(inventory.remaining()[0].remaining, 0);
+    }
+}
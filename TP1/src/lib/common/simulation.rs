@@ -0,0 +1,95 @@
+//! Webservice simulation policy shared by both implementations.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::common::config::WebServiceConfig;
+
+// CONSTANTS ------------------------------------------------------------------
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// TYPES ----------------------------------------------------------------------
+
+/// Simulation decides how long each fetch to a webservice takes (in secs)
+/// and whether it succeeds, according to the webservice config.
+
+pub struct Simulation {
+    name: String,
+    seed: Option<u64>,
+    failure_rate: f64,
+    min_delay: u64,
+    max_delay: u64,
+    rng: StdRng,
+}
+
+/// Outcome of a simulated fetch.
+pub struct FetchOutcome {
+    pub delay: u64,
+    pub succeeded: bool,
+}
+
+// FUNCTIONS ------------------------------------------------------------------
+
+impl Simulation {
+    /// Given a WebServiceConfig and an optional seed this method creates the
+    /// simulation for that webservice.
+
+    pub fn from_config(config: &WebServiceConfig, seed: Option<u64>) -> Self {
+        Simulation {
+            name: config.name.clone(),
+            seed,
+            failure_rate: config.failure_rate,
+            min_delay: config.min_delay,
+            max_delay: config.max_delay,
+            rng: webservice_rng(seed, &config.name),
+        }
+    }
+
+    /// Given a key that identifies a booking (e.g. its request and route) this method creates
+    /// the simulation of that booking alone. With a seed, its RNG is derived from the seed and
+    /// the key, so its fetches do not depend on the order concurrent bookings are fetched in.
+
+    pub fn for_booking(&self, key: &str) -> Simulation {
+        Simulation {
+            name: self.name.clone(),
+            seed: self.seed,
+            failure_rate: self.failure_rate,
+            min_delay: self.min_delay,
+            max_delay: self.max_delay,
+            rng: webservice_rng(self.seed.map(|seed| seed ^ stable_hash(key)), &self.name),
+        }
+    }
+
+    /// Draws the next fetch outcome. Both the delay and the result are drawn
+    /// together, so each fetch consumes the same part of the sequence.
+
+    pub fn next_fetch(&mut self) -> FetchOutcome {
+        let delay = self.rng.gen_range(self.min_delay..self.max_delay);
+        let coin = self.rng.gen_range(0.0..1.0);
+
+        FetchOutcome {
+            delay,
+            succeeded: coin > self.failure_rate,
+        }
+    }
+}
+
+/// Given an optional general seed and a webservice name this function returns its RNG.
+/// With a seed, each webservice gets its own RNG seeded from the seed and its name, so runs
+/// are reproducible. Without one, the RNG is seeded from entropy.
+
+pub fn webservice_rng(seed: Option<u64>, name: &str) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed ^ stable_hash(name)),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// FNV-1a hash, stable across runs and platforms (unlike std's DefaultHasher).
+
+fn stable_hash(value: &str) -> u64 {
+    value.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
@@ -1,3 +1,4 @@
 pub mod common;
+pub mod loadgen;
 pub mod part1;
 pub mod part2;
@@ -0,0 +1,3 @@
+// Public
+pub mod report;
+pub mod runner;
@@ -0,0 +1,116 @@
+//! Load generator results.
+
+use std::{collections::BTreeMap, fmt};
+
+/// Report is an entity that accumulates the outcome of every request sent
+/// by the load generator, and knows how to summarize it.
+
+#[derive(Default)]
+pub struct Report {
+    pub total: usize,
+    pub submitted: u64,
+    pub completed: u64,
+    pub rejected: u64,
+    pub elapsed_ms: i64,
+    latencies_ms: Vec<i64>,
+    errors: BTreeMap<String, u64>,
+}
+
+impl Report {
+    pub fn new(total: usize) -> Self {
+        Report {
+            total,
+            ..Default::default()
+        }
+    }
+
+    /// Registers a booking that completed with the given end-to-end latency.
+
+    pub fn add_completed(&mut self, latency_ms: i64) {
+        self.completed += 1;
+        self.latencies_ms.push(latency_ms);
+    }
+
+    /// Registers a booking that was rejected (also a completed one), with the given end-to-end latency.
+
+    pub fn add_rejected(&mut self, latency_ms: i64) {
+        self.rejected += 1;
+        self.add_completed(latency_ms);
+    }
+
+    /// Registers an error of the given kind.
+
+    pub fn add_error(&mut self, kind: String) {
+        *self.errors.entry(kind).or_insert(0) += 1;
+    }
+
+    pub fn n_errors(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    /// Returns the latency (in ms) for the given percentile (0-100), using nearest rank.
+
+    pub fn percentile(&self, p: f64) -> Option<i64> {
+        let mut latencies = self.latencies_ms.clone();
+        latencies.sort_unstable();
+        percentile(&latencies, p)
+    }
+}
+
+fn percentile(sorted: &[i64], p: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let elapsed_secs = self.elapsed_ms as f64 / 1000.0;
+        let throughput = match self.elapsed_ms > 0 {
+            true => self.completed as f64 / elapsed_secs,
+            false => 0.0,
+        };
+
+        writeln!(f, "{:=^55}", " LOADGEN REPORT ")?;
+        writeln!(f, "Requests in file:     {}", self.total)?;
+        writeln!(f, "Requests submitted:   {}", self.submitted)?;
+        writeln!(f, "Bookings completed:   {}", self.completed)?;
+        writeln!(f, "  Rejected:           {}", self.rejected)?;
+        writeln!(f, "Errors:               {}", self.n_errors())?;
+        writeln!(f, "Elapsed time:         {:.2} s", elapsed_secs)?;
+        writeln!(f, "Throughput:           {:.2} bookings/s", throughput)?;
+
+        let mut latencies = self.latencies_ms.clone();
+        latencies.sort_unstable();
+        if !latencies.is_empty() {
+            let mean = latencies.iter().sum::<i64>() / latencies.len() as i64;
+            writeln!(f, "{:-^55}", " END-TO-END LATENCY (ms) ")?;
+            writeln!(
+                f,
+                "|{:^8}|{:^8}|{:^8}|{:^8}|{:^8}|{:^8}|",
+                "min", "p50", "p90", "p95", "p99", "max"
+            )?;
+            writeln!(
+                f,
+                "|{:^8}|{:^8}|{:^8}|{:^8}|{:^8}|{:^8}|",
+                latencies[0],
+                percentile(&latencies, 50.0).unwrap_or(0),
+                percentile(&latencies, 90.0).unwrap_or(0),
+                percentile(&latencies, 95.0).unwrap_or(0),
+                percentile(&latencies, 99.0).unwrap_or(0),
+                latencies[latencies.len() - 1]
+            )?;
+            writeln!(f, "Mean: {} ms", mean)?;
+        }
+
+        if !self.errors.is_empty() {
+            writeln!(f, "{:-^55}", " ERRORS ")?;
+            for (kind, count) in &self.errors {
+                writeln!(f, "{:>6}  {}", count, kind)?;
+            }
+        }
+        write!(f, "{:=^55}", "")
+    }
+}
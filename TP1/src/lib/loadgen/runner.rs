@@ -0,0 +1,212 @@
+//! Load generator runner.
+//!
+//! The main thread submits the requests (`POST /request`), respecting the
+//! configured rate and the maximum amount of in-flight bookings. A poller thread
+//! checks each submitted booking (`GET /request`) until it completes or times out.
+
+use std::{
+    sync::{
+        mpsc::{channel, Receiver, TryRecvError},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use std_semaphore::Semaphore;
+
+use crate::common::{
+    http_client::{self, Url},
+    request::RawRequest,
+    utils::now,
+};
+use crate::loadgen::report::Report;
+
+// CONSTANTS ------------------------------------------------------------------
+
+const IN_PROGRESS_STATUS: &str = "PENDING";
+const COMPLETED_STATUS: &str = "COMPLETED";
+const REJECTED_STATUS: &str = "REJECTED";
+
+// TYPES ----------------------------------------------------------------------
+
+pub struct LoadGenConfig {
+    /// Base URL of the booking API (e.g. `http://localhost:8080`).
+    pub url: Url,
+    /// Maximum requests submitted per second (unlimited if None).
+    pub rate: Option<f64>,
+    /// Maximum bookings in flight (submitted but not completed).
+    pub concurrency: usize,
+    /// Time between polling rounds, in ms.
+    pub poll_interval: u64,
+    /// Time to wait for a booking to complete, in secs.
+    pub booking_timeout: u64,
+    /// Timeout for each HTTP call, in ms.
+    pub http_timeout: u64,
+}
+
+struct Submitted {
+    id: String,
+    start_time: i64,
+}
+
+// FUNCTIONS ------------------------------------------------------------------
+
+/// Given a LoadGenConfig and the requests to replay this function sends
+/// every request to the API and waits for all of them, returning the Report.
+
+pub fn run(config: LoadGenConfig, requests: Vec<RawRequest>) -> Report {
+    let report = Arc::new(Mutex::new(Report::new(requests.len())));
+    let in_flight = Arc::new(Semaphore::new(config.concurrency.max(1) as isize));
+    let http_timeout = Duration::from_millis(config.http_timeout);
+    let (tx, rx) = channel::<Submitted>();
+
+    let poller = spawn_poller(
+        rx,
+        config.url.with_path("/request"),
+        Duration::from_millis(config.poll_interval),
+        config.booking_timeout as i64 * 1000,
+        http_timeout,
+        report.clone(),
+        in_flight.clone(),
+    );
+
+    let post_url = config.url.with_path("/request");
+    let ts_start = now();
+    for (i, raw_request) in requests.iter().enumerate() {
+        in_flight.acquire();
+
+        if let Some(rate) = config.rate {
+            let slot = ts_start + (i as f64 * 1000.0 / rate) as i64;
+            let wait = slot - now();
+            if wait > 0 {
+                thread::sleep(Duration::from_millis(wait as u64));
+            }
+        }
+
+        let body = serde_json::to_string(raw_request).expect("[CRITICAL] Invalid request");
+        let start_time = now();
+        let result = http_client::post_json(&post_url, &body, http_timeout);
+
+        let mut report = lock(&report);
+        match result {
+            Ok(res) if res.status == 201 => {
+                report.submitted += 1;
+                let _ = tx.send(Submitted {
+                    id: res.body,
+                    start_time,
+                });
+            }
+            Ok(res) => {
+                report.add_error(format!("submit: HTTP {} ({})", res.status, res.body));
+                in_flight.release();
+            }
+            Err(err) => {
+                report.add_error(format!("submit: {}", err.kind()));
+                in_flight.release();
+            }
+        }
+    }
+
+    drop(tx);
+    poller
+        .join()
+        .expect("[CRITICAL] Error joining loadgen poller thread");
+
+    let mut report = lock(&report);
+    report.elapsed_ms = now() - ts_start;
+    std::mem::take(&mut *report)
+}
+
+fn spawn_poller(
+    rx: Receiver<Submitted>,
+    url: Url,
+    poll_interval: Duration,
+    booking_timeout_ms: i64,
+    http_timeout: Duration,
+    report: Arc<Mutex<Report>>,
+    in_flight: Arc<Semaphore>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut pending: Vec<Submitted> = Vec::new();
+        let mut submitter_done = false;
+
+        while !submitter_done || !pending.is_empty() {
+            loop {
+                match rx.try_recv() {
+                    Ok(submitted) => pending.push(submitted),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        submitter_done = true;
+                        break;
+                    }
+                }
+            }
+
+            pending.retain(|submitted| {
+                let finished = poll(submitted, &url, booking_timeout_ms, http_timeout, &report);
+                if finished {
+                    in_flight.release();
+                }
+                !finished
+            });
+
+            thread::sleep(poll_interval);
+        }
+    })
+}
+
+/// Polls a booking once, returning whether it finished (successfully or not).
+
+fn poll(
+    Submitted { id, start_time }: &Submitted,
+    url: &Url,
+    booking_timeout_ms: i64,
+    http_timeout: Duration,
+    report: &Arc<Mutex<Report>>,
+) -> bool {
+    let url = url.with_path(&format!("{}?id={}", url.path, id));
+    let result = http_client::get(&url, http_timeout);
+    let elapsed = now() - start_time;
+
+    let mut report = lock(report);
+    match result {
+        Ok(res) if res.status == 200 => {
+            let status = serde_json::from_str::<serde_json::Value>(&res.body)
+                .ok()
+                .and_then(|value| value["status"].as_str().map(String::from))
+                .unwrap_or_default();
+
+            match status.as_str() {
+                COMPLETED_STATUS => {
+                    report.add_completed(elapsed);
+                    return true;
+                }
+                // A rejection (e.g. a sold out flight) is a normal outcome of the booking
+                REJECTED_STATUS => {
+                    report.add_rejected(elapsed);
+                    return true;
+                }
+                IN_PROGRESS_STATUS => (),
+                other => {
+                    report.add_error(format!("booking: finished with status '{}'", other));
+                    return true;
+                }
+            }
+        }
+        Ok(res) => report.add_error(format!("poll: HTTP {}", res.status)),
+        Err(err) => report.add_error(format!("poll: {}", err.kind())),
+    }
+
+    if elapsed > booking_timeout_ms {
+        report.add_error("booking: timed out".to_string());
+        return true;
+    }
+    false
+}
+
+fn lock(report: &Arc<Mutex<Report>>) -> MutexGuard<'_, Report> {
+    report
+        .lock()
+        .expect("[CRITICAL] Could not take loadgen report lock")
+}
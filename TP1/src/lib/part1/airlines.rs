@@ -1,45 +1,49 @@
 //! Airline Webservice.
-use std::{collections::HashMap, error::Error};
+use std::collections::HashMap;
 
-use crate::common::config::{AirlineConfig, AirlinesConfig};
-use crate::part1::{logger::LoggerSender, webservice::WebService};
+use crate::common::config::AirlinesConfig;
+use crate::part1::{
+    logger::LoggerSender,
+    shutdown::Shutdown,
+    webservice::{SharedInventory, WebService},
+};
 
 pub type Airline = WebService;
 
 pub type Airlines = HashMap<String, Airline>;
 
-/// Given a String representing a system path and a sender for the Logger
+/// Seat inventory of each airline with limited seats, sorted by airline name.
+pub type Inventories = Vec<(String, SharedInventory)>;
+
+/// Given an AirlinesConfig, an optional seed, a sender for the Logger and the Shutdown flag
 /// this method will create a map of airles that will handle each request correspondingly.
 /// Each Airline is a WebService that controls the rate limit and simulates the fetch to the hotel provider.
 
-pub fn from_path(path: &str, logger_sender: LoggerSender) -> Result<Airlines, Box<dyn Error>> {
+pub fn from_config(
+    airlines: AirlinesConfig,
+    seed: Option<u64>,
+    logger_sender: LoggerSender,
+    shutdown: Shutdown,
+) -> Airlines {
     let mut content = Airlines::new();
 
-    let data = std::fs::read_to_string(path)?;
-    let airlines: AirlinesConfig = serde_json::from_str(&data)?;
-
-    for AirlineConfig {
-        name,
-        rate_limit,
-        failure_rate,
-        retry_time,
-        min_delay,
-        max_delay,
-    } in airlines
-    {
+    for airline in airlines {
         content.insert(
-            name.clone(),
-            WebService::new(
-                name,
-                rate_limit,
-                failure_rate,
-                retry_time,
-                logger_sender.clone(),
-                min_delay,
-                max_delay,
-            ),
+            airline.name.clone(),
+            WebService::from_config(airline, seed, logger_sender.clone(), shutdown.clone()),
         );
     }
 
-    Ok(content)
+    content
+}
+
+/// Returns the seat inventory of every airline with declared flights.
+
+pub fn inventories(airlines: &Airlines) -> Inventories {
+    let mut inventories: Inventories = airlines
+        .iter()
+        .filter_map(|(name, airline)| Some((name.clone(), airline.inventory()?)))
+        .collect();
+    inventories.sort_by(|a, b| a.0.cmp(&b.0));
+    inventories
 }
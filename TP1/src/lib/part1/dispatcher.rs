@@ -1,22 +1,278 @@
-//! File request parser and dispatcher
+//! Request parser and dispatcher
 
-use std::error::Error;
+use std::{
+    convert::TryFrom,
+    error::Error,
+    io::Read,
+    sync::mpsc::{sync_channel, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
 
-use crate::part1::{request::Request, request_handler::RequestHandler};
+use crate::common::request::{CsvRequest, RawRequest};
+use crate::part1::{
+    input::Input,
+    logger::LoggerSender,
+    rejects::{Rejected, RejectsWriter},
+    request_handler::RequestHandler,
+    shutdown::Shutdown,
+};
 
-/// Given a String representing a system file path and a RequestHandler
-/// this method will parse the file (csv type required) into a Request struct and
-/// call the handler in RequestHandler to process the request.
+/// Time between shutdown checks while waiting for the next request.
+const SHUTDOWN_POLL_PERIOD: Duration = Duration::from_millis(200);
 
-pub fn from_path(path: String, req_handler: &mut RequestHandler) -> Result<(), Box<dyn Error>> {
-    let mut rdr = csv::Reader::from_path(path)?;
-    for result in rdr.deserialize() {
-        let req: Request = result?;
-        let req_clone: Request = req.clone();
-        if req_handler.handle(req_clone).is_err() {
-            println!("[WARNING] Ignoring invalid request: {:#?}", req);
+/// Input row, parsed (along with its line and content) or rejected.
+enum Row {
+    Request(u64, RawRequest, String),
+    Rejected(Rejected),
+}
+
+/// Given an Input (file, stdin or followed file), a RequestHandler, a RejectsWriter, a sender for
+/// the Logger and the Shutdown flag this method will parse its content (csv type required) into
+/// RawRequest structs and call the handler in RequestHandler to process each request as soon as its
+/// line arrives. Malformed rows and requests the handler can not process are sent to the RejectsWriter.
+/// Stops dispatching when the shutdown is requested, even if the input is blocked waiting for data.
+/// Once done, logs how many requests were dispatched and rejected.
+
+pub fn from_input(
+    input: &Input,
+    req_handler: &mut RequestHandler,
+    mut rejects: RejectsWriter,
+    logger_sender: &LoggerSender,
+    shutdown: &Shutdown,
+) -> Result<(), Box<dyn Error>> {
+    let dispatched = from_reader(
+        input.open()?,
+        req_handler,
+        &mut rejects,
+        logger_sender,
+        shutdown,
+    )?;
+
+    logger_sender.send(match rejects.count() {
+        0 => format!("Dispatched {} requests, none rejected", dispatched),
+        rejected => format!(
+            "Dispatched {} requests, rejected {} (see {})",
+            dispatched,
+            rejected,
+            rejects.path()
+        ),
+    });
+    Ok(())
+}
+
+/// Dispatches the requests read from the given reader, returning how many were dispatched.
+
+fn from_reader(
+    reader: Box<dyn Read + Send>,
+    req_handler: &mut RequestHandler,
+    rejects: &mut RejectsWriter,
+    logger_sender: &LoggerSender,
+    shutdown: &Shutdown,
+) -> Result<u64, Box<dyn Error>> {
+    let rows = spawn_reader(reader);
+    let mut dispatched: u64 = 0;
+
+    loop {
+        if shutdown.is_requested() {
+            logger_sender.send(String::from(
+                "[WARNING] Shutdown requested: no more requests will be dispatched and pending retries are cancelled (signal again to force exit)",
+            ));
+            break;
+        }
+
+        let row = match rows.recv_timeout(SHUTDOWN_POLL_PERIOD) {
+            Ok(row) => row?,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
         };
+        match row {
+            Row::Request(line, req, row) => match req_handler.handle(line, req) {
+                Ok(()) => dispatched += 1,
+                Err(err) => rejects.write(Rejected {
+                    line,
+                    reason: err.to_string(),
+                    row,
+                }),
+            },
+            Row::Rejected(rejected) => rejects.write(rejected),
+        }
     }
 
-    Ok(())
+    Ok(dispatched)
+}
+
+/// Parses the input in its own thread, which is left behind if the shutdown
+/// is requested while it is blocked reading. Only reading errors are fatal.
+
+fn spawn_reader(reader: Box<dyn Read + Send>) -> Receiver<csv::Result<Row>> {
+    let (tx, rx) = sync_channel::<csv::Result<Row>>(0);
+
+    thread::spawn(move || {
+        let mut rdr = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+        let headers = match rdr.byte_headers() {
+            Ok(headers) => headers.clone(),
+            Err(err) => {
+                let _ = tx.send(Err(err));
+                return;
+            }
+        };
+
+        for result in rdr.byte_records() {
+            let parsed = result.map(|record| parse_record(&record, &headers));
+            let failed = parsed.is_err();
+            if tx.send(parsed).is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    rx
+}
+
+fn parse_record(record: &csv::ByteRecord, headers: &csv::ByteRecord) -> Row {
+    let line = record.position().map_or(0, |position| position.line());
+    let row = record
+        .iter()
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(",");
+
+    if record.len() != headers.len() {
+        return Row::Rejected(Rejected {
+            line,
+            reason: format!("expected {} fields, found {}", headers.len(), record.len()),
+            row,
+        });
+    }
+
+    let parsed = record
+        .deserialize::<CsvRequest>(Some(headers))
+        .map_err(|err| match err.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => {
+                match err.field().and_then(|field| headers.get(field as usize)) {
+                    Some(field) => format!("{}: {}", String::from_utf8_lossy(field), err.kind()),
+                    None => err.to_string(),
+                }
+            }
+            _ => err.to_string(),
+        })
+        .and_then(RawRequest::try_from);
+
+    match parsed {
+        Ok(req) => Row::Request(line, req, row),
+        Err(reason) => Row::Rejected(Rejected { line, reason, row }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, io::Cursor, process};
+
+    use super::*;
+    use crate::common::{
+        config::{LoggerConfig, MetricsCollectorConfig, RequestHandlerConfig, WebServiceConfig},
+        utils::uuid,
+    };
+    use crate::part1::{
+        airlines, hotel, logger::Logger, metrics_collector::MetricsCollector,
+        webservice::WebService,
+    };
+
+    fn webservice_config(name: &str) -> WebServiceConfig {
+        WebServiceConfig {
+            name: name.to_string(),
+            rate_limit: 2,
+            failure_rate: 0.0,
+            retry_time: 1,
+            min_delay: 0,
+            max_delay: 1,
+            flights: None,
+        }
+    }
+
+    #[test]
+    fn malformed_rows_are_rejected_and_the_rest_dispatched() {
+        let dirpath = env::temp_dir()
+            .join(format!("tp1-dispatcher-{}-{}", process::id(), uuid()))
+            .to_string_lossy()
+            .to_string();
+        let logger = Logger::from_config(LoggerConfig {
+            dirpath: dirpath.clone(),
+        })
+        .expect("Could not start logger");
+        let shutdown = Shutdown::new();
+        let airlines = airlines::from_config(
+            vec![webservice_config("Iberia")],
+            Some(1),
+            logger.get_sender(),
+            shutdown.clone(),
+        );
+        let hotel = hotel::from_config(
+            webservice_config("Hotel"),
+            Some(1),
+            logger.get_sender(),
+            shutdown.clone(),
+        );
+        let webservices: Vec<WebService> = vec![airlines["Iberia"].clone(), hotel.clone()];
+        let metrics_collector = MetricsCollector::from_config(
+            MetricsCollectorConfig {
+                printer_period: 100,
+                n_most_booked: 3,
+            },
+            Vec::new(),
+            webservices,
+            logger.get_sender(),
+        )
+        .expect("Could not start metrics collector");
+        let mut req_handler = RequestHandler::new(
+            RequestHandlerConfig {
+                pool_size: 2,
+                queue_size: 4,
+            },
+            airlines,
+            hotel,
+            logger.get_sender(),
+            metrics_collector.get_sender(),
+            None,
+        );
+
+        let csv = "origin,destiny,airline,package\n\
+            EZE,MAD,Iberia,false\n\
+            EZE,MAD,Iberia\n\
+            EZE,MAD,Unknown Air,false\n\
+            EZE,MAD,Iberia,maybe\n\
+            EZE,BCN,Iberia,true\n";
+        let rejects_path = format!("{}/rejects.csv", dirpath);
+        let mut rejects = RejectsWriter::new(rejects_path.clone(), logger.get_sender());
+        let dispatched = from_reader(
+            Box::new(Cursor::new(csv)),
+            &mut req_handler,
+            &mut rejects,
+            &logger.get_sender(),
+            &shutdown,
+        )
+        .expect("Could not dispatch");
+        let rejected = rejects.count();
+        drop(rejects);
+        req_handler.join();
+        metrics_collector.join();
+        logger.join();
+
+        assert_eq!((dispatched, rejected), (2, 3));
+        let rejected: Vec<(u64, String, String)> = csv::Reader::from_path(&rejects_path)
+            .expect("Could not open rejects file")
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .expect("Invalid rejects file");
+        let lines: Vec<u64> = rejected.iter().map(|(line, _, _)| *line).collect();
+        assert_eq!(lines, vec![3, 4, 5]);
+        assert_eq!(rejected[0].1, "expected 4 fields, found 3");
+        assert_eq!(rejected[0].2, "EZE,MAD,Iberia");
+        assert_eq!(rejected[1].1, "unknown airline 'Unknown Air'");
+        assert!(rejected[2].1.starts_with("package: "), "{}", rejected[2].1);
+        assert_eq!(rejected[2].2, "EZE,MAD,Iberia,maybe");
+
+        fs::remove_dir_all(dirpath).ok();
+    }
 }
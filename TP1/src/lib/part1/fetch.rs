@@ -1,20 +1,29 @@
-use rand::Rng;
+use crate::common::{
+    inventory::BookingRejection,
+    simulation::{FetchOutcome, Simulation},
+};
+use crate::part1::shutdown::Shutdown;
 
-use crate::common::utils::*;
-
-pub struct FetchError;
+pub enum FetchError {
+    /// The webservice answered with an error.
+    Failed,
+    /// The fetch was interrupted by a shutdown.
+    Cancelled,
+    /// The webservice definitively rejected the booking.
+    Rejected(BookingRejection),
+}
 
-pub fn simulate_fetch(failure_rate: f64, min_delay: u64, max_delay: u64) -> Result<(), FetchError> {
-    let mut rng = rand::thread_rng();
+pub fn simulate_fetch(simulation: &mut Simulation, shutdown: &Shutdown) -> Result<(), FetchError> {
+    let FetchOutcome { delay, succeeded } = simulation.next_fetch();
 
     // Simulate fetch
-    let fetch_time = rng.gen_range(min_delay..max_delay);
-    sleep(fetch_time);
+    if !shutdown.sleep(delay) {
+        return Err(FetchError::Cancelled);
+    }
 
     // Simulate status
-    let coin = rng.gen_range(0.0..1.0);
-    match coin > failure_rate {
+    match succeeded {
         true => Ok(()),
-        false => Err(FetchError),
+        false => Err(FetchError::Failed),
     }
 }
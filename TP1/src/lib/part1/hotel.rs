@@ -1,34 +1,18 @@
 //! Hotel Webservice.
-use std::error::Error;
-
 use crate::common::config::HotelConfig;
-use crate::part1::{logger::LoggerSender, webservice::WebService};
+use crate::part1::{logger::LoggerSender, shutdown::Shutdown, webservice::WebService};
 
 pub type Hotel = WebService;
 
-/// Given a String representing a system path and a sender for the Logger
+/// Given a HotelConfig, an optional seed, a sender for the Logger and the Shutdown flag
 /// this method will create a hotel webservice that will handle each request correspondingly.
 /// The Hotel is a WebService that controls the rate limit and simulates the fetch to the hotel provider.
 
-pub fn from_path(path: &str, logger_sender: LoggerSender) -> Result<Hotel, Box<dyn Error>> {
-    let data = std::fs::read_to_string(path)?;
-
-    let HotelConfig {
-        name,
-        rate_limit,
-        failure_rate,
-        retry_time,
-        min_delay,
-        max_delay,
-    } = serde_json::from_str(&data)?;
-
-    Ok(WebService::new(
-        name,
-        rate_limit,
-        failure_rate,
-        retry_time,
-        logger_sender,
-        min_delay,
-        max_delay,
-    ))
+pub fn from_config(
+    config: HotelConfig,
+    seed: Option<u64>,
+    logger_sender: LoggerSender,
+    shutdown: Shutdown,
+) -> Hotel {
+    WebService::from_config(config, seed, logger_sender, shutdown)
 }
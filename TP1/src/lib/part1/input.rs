@@ -0,0 +1,181 @@
+//! Requests input sources.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    thread,
+    time::Duration,
+};
+
+// CONSTANTS ------------------------------------------------------------------
+
+/// Path that represents the standard input.
+pub const STDIN_PATH: &str = "-";
+
+/// Time to wait for new data when following a file.
+const FOLLOW_POLL_PERIOD: Duration = Duration::from_millis(500);
+
+// TYPES ----------------------------------------------------------------------
+
+/// Source to read the requests (csv format) from.
+pub enum Input {
+    /// Read the standard input until EOF.
+    Stdin,
+    /// Read a file until EOF.
+    File(String),
+    /// Read a file, waiting for new lines at EOF (like `tail -f`).
+    Follow(String),
+}
+
+/// FollowReader is a reader over a file that never reaches EOF: when there is
+/// no more data it waits for the file to grow. If the file gets truncated it
+/// starts reading again from the beginning, skipping the csv header already read.
+/// Files replaced by another one (e.g. rotated by renaming) are not detected:
+/// it keeps reading the replaced file.
+
+pub struct FollowReader {
+    file: File,
+    position: u64,
+    skipping_header: bool,
+}
+
+// FUNCTIONS ------------------------------------------------------------------
+
+impl Input {
+    /// Given a path (`-` for stdin) and whether it should be followed this method returns the Input.
+
+    pub fn new(path: String, follow: bool) -> Result<Input, Box<dyn Error>> {
+        match (path.as_str(), follow) {
+            (STDIN_PATH, true) => Err("Follow mode is not supported for stdin".into()),
+            (STDIN_PATH, false) => Ok(Input::Stdin),
+            (_, true) => Ok(Input::Follow(path)),
+            (_, false) => Ok(Input::File(path)),
+        }
+    }
+
+    /// Opens the input, returning a reader over it.
+
+    pub fn open(&self) -> io::Result<Box<dyn Read + Send>> {
+        match self {
+            Input::Stdin => Ok(Box::new(io::stdin())),
+            Input::File(path) => Ok(Box::new(File::open(path)?)),
+            Input::Follow(path) => Ok(Box::new(FollowReader {
+                file: File::open(path)?,
+                position: 0,
+                skipping_header: false,
+            })),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Input::Stdin => "stdin".to_string(),
+            Input::File(path) => format!("file (path: {})", path),
+            Input::Follow(path) => format!("file in follow mode (path: {})", path),
+        }
+    }
+}
+
+impl Read for FollowReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let mut n = self.file.read(buf)?;
+            self.position += n as u64;
+            if n > 0 && self.skipping_header {
+                // The rows after the header are moved to the start of the buffer
+                match buf[..n].iter().position(|&byte| byte == b'\n') {
+                    Some(header_end) => {
+                        buf.copy_within(header_end + 1..n, 0);
+                        n -= header_end + 1;
+                        self.skipping_header = false;
+                    }
+                    None => n = 0,
+                }
+                if n == 0 {
+                    continue;
+                }
+            }
+            if n > 0 {
+                return Ok(n);
+            }
+
+            if self.file.metadata()?.len() < self.position {
+                self.position = self.file.seek(SeekFrom::Start(0))?;
+                self.skipping_header = true;
+                continue;
+            }
+            thread::sleep(FOLLOW_POLL_PERIOD);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        fs::{self, OpenOptions},
+        io::{BufRead, BufReader, Write},
+        process,
+        sync::mpsc,
+    };
+
+    use super::*;
+    use crate::common::utils::uuid;
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    fn append(path: &str, data: &str) {
+        OpenOptions::new()
+            .append(true)
+            .open(path)
+            .expect("Could not open requests file")
+            .write_all(data.as_bytes())
+            .expect("Could not append to requests file");
+    }
+
+    #[test]
+    fn follow_reads_appended_rows_and_skips_the_header_after_truncation() {
+        let path = env::temp_dir()
+            .join(format!("tp1-follow-{}-{}.csv", process::id(), uuid()))
+            .to_string_lossy()
+            .to_string();
+        fs::write(
+            &path,
+            "origin,destiny,airline,package\nEZE,MAD,Iberia,false\n",
+        )
+        .expect("Could not write requests file");
+
+        // The reader never reaches EOF, so its thread is left waiting once the test ends
+        let reader = Input::new(path.clone(), true)
+            .expect("Invalid input")
+            .open()
+            .expect("Could not open requests file");
+        let (lines_tx, lines_rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                if lines_tx.send(line.expect("Could not read line")).is_err() {
+                    return;
+                }
+            }
+        });
+        let next_line = || lines_rx.recv_timeout(WAIT).expect("No line read");
+
+        assert_eq!(next_line(), "origin,destiny,airline,package");
+        assert_eq!(next_line(), "EZE,MAD,Iberia,false");
+
+        append(&path, "EZE,BCN,Iberia,false\nEZE,");
+        assert_eq!(next_line(), "EZE,BCN,Iberia,false");
+        append(&path, "FCO,Alitalia,true\n");
+        assert_eq!(next_line(), "EZE,FCO,Alitalia,true");
+
+        fs::write(
+            &path,
+            "origin,destiny,airline,package\nEZE,LIM,LATAM,false\n",
+        )
+        .expect("Could not truncate requests file");
+        assert_eq!(next_line(), "EZE,LIM,LATAM,false");
+
+        fs::remove_file(&path).ok();
+    }
+}
@@ -4,9 +4,12 @@
 //! * Mean request time
 //! * Top n routes
 //! * Number of requests
+//! * Number of unfinished requests
+//! * Number of rejected requests
+//! * Remaining seats of each airline with declared flights
+//! * Queued and in-flight fetches, attempts, failures and retry wait time of each webservice
 
 use std::{
-    collections::HashMap,
     error::Error,
     sync::mpsc::{channel, Receiver, Sender},
     sync::{Arc, RwLock},
@@ -14,25 +17,40 @@ use std::{
     time,
 };
 
-use crate::common::{config::MetricsCollectorConfig, utils};
-use crate::part1::request::{Request, RequestDuration};
+use crate::common::{
+    config::MetricsCollectorConfig,
+    inventory::format_inventory,
+    metrics::{format_most_booked_routes, format_webservices_metrics, Metrics, WebServicesMetrics},
+    request::RawRequest,
+    utils,
+};
+use crate::part1::{airlines::Inventories, logger::LoggerSender, webservice::WebService};
+
+pub type RequestDuration = (RawRequest, i64);
 
-struct Metrics {
-    routes_booking_count: HashMap<(String, String), u64>,
-    reqs_duration_cumsum: i64,
-    n_reqs: u64,
+/// Message received by the metrics collector thread.
+pub enum MetricsMessage {
+    Finished(RequestDuration),
+    Unfinished(RawRequest),
+    Rejected(RawRequest),
 }
 
 /// MetricsCollector is an entity that keeps a reference to the threads
 /// that handle the metrics. One of the threads will be in charge of collecting
 /// metrics using a channel for communication, the other will be in charge of printing
 /// to stdout periodically the metrics collected. Both threads get syncronized using a RwLock.
+/// When joined, the final summary is sent to the Logger.
 
 pub struct MetricsCollector {
     collector_handler: JoinHandle<()>,
     keep_running: Arc<RwLock<bool>>,
     printer_handler: JoinHandle<()>,
-    tx: Sender<RequestDuration>,
+    tx: Sender<MetricsMessage>,
+    metrics: Arc<RwLock<Metrics>>,
+    n_most_booked: usize,
+    inventories: Inventories,
+    webservices: Vec<WebService>,
+    logger_sender: LoggerSender,
 }
 
 /// MetricsSender holds a reference to the channel that handles the communication
@@ -40,33 +58,42 @@ pub struct MetricsCollector {
 
 #[derive(Clone)]
 pub struct MetricsSender {
-    tx: Sender<RequestDuration>,
+    tx: Sender<MetricsMessage>,
 }
 
 impl MetricsCollector {
-    /// Given a MetricsCollectorConfig this method will create a MetricsCollector entity,
-    /// and spawn the corresponding threads.
+    /// Given a MetricsCollectorConfig, the airlines seat inventories, the webservices and a sender
+    /// for the Logger this method will create a MetricsCollector entity, and spawn the corresponding threads.
 
-    pub fn from_config(config: MetricsCollectorConfig) -> Result<MetricsCollector, Box<dyn Error>> {
-        let (tx, rx): (Sender<RequestDuration>, Receiver<RequestDuration>) = channel();
+    pub fn from_config(
+        MetricsCollectorConfig {
+            printer_period,
+            n_most_booked,
+        }: MetricsCollectorConfig,
+        inventories: Inventories,
+        webservices: Vec<WebService>,
+        logger_sender: LoggerSender,
+    ) -> Result<MetricsCollector, Box<dyn Error>> {
+        let (tx, rx): (Sender<MetricsMessage>, Receiver<MetricsMessage>) = channel();
         let keep_running = Arc::new(RwLock::new(true));
-        let metrics = Arc::new(RwLock::new(Metrics {
-            routes_booking_count: HashMap::new(),
-            reqs_duration_cumsum: 0,
-            n_reqs: 0,
-        }));
+        let metrics = Arc::new(RwLock::new(Metrics::new()));
 
         let collector_metrics = metrics.clone();
+        let printer_metrics = metrics.clone();
         let printer_keep_running = keep_running.clone();
+        let printer_inventories = inventories.clone();
+        let printer_webservices = webservices.clone();
 
         let collector_handler =
             spawn(move || MetricsCollector::collect_metrics(rx, collector_metrics));
         let printer_handler = spawn(move || {
             MetricsCollector::print_metrics_periodically(
-                metrics,
+                printer_metrics,
                 printer_keep_running,
-                time::Duration::from_millis(config.printer_period),
-                config.n_most_booked,
+                time::Duration::from_millis(printer_period),
+                n_most_booked,
+                printer_inventories,
+                printer_webservices,
             )
         });
 
@@ -75,88 +102,100 @@ impl MetricsCollector {
             keep_running,
             printer_handler,
             tx,
+            metrics,
+            n_most_booked,
+            inventories,
+            webservices,
+            logger_sender,
         };
 
         Ok(metrics_collector)
     }
 
-    fn collect_metrics(rx: Receiver<RequestDuration>, metrics: Arc<RwLock<Metrics>>) {
-        while let Ok((req, time)) = rx.recv() {
-            MetricsCollector::compute_request(req, time, &metrics);
+    fn collect_metrics(rx: Receiver<MetricsMessage>, metrics: Arc<RwLock<Metrics>>) {
+        while let Ok(msg) = rx.recv() {
+            MetricsCollector::compute_message(msg, &metrics);
         }
     }
 
-    fn compute_request(req: Request, time: i64, metrics_lock: &Arc<RwLock<Metrics>>) {
-        let route_key = (req.origin, req.destiny);
+    fn compute_message(msg: MetricsMessage, metrics_lock: &Arc<RwLock<Metrics>>) {
         let mut metrics = metrics_lock
             .write()
             .expect("[CRITICAL] Could not take metrics write lock");
 
-        metrics.n_reqs += 1;
-        metrics.reqs_duration_cumsum += time;
-
-        if let Some(route_count) = metrics.routes_booking_count.get_mut(&route_key) {
-            *route_count += 1;
-        } else {
-            metrics.routes_booking_count.insert(route_key, 1);
+        match msg {
+            MetricsMessage::Finished((req, time)) => {
+                metrics.add_request(req.booked_routes(), req.party_size(), time)
+            }
+            MetricsMessage::Unfinished(_) => metrics.add_unfinished(),
+            MetricsMessage::Rejected(_) => metrics.add_rejected(),
         }
     }
 
-    fn get_n_most_booked_routes(
-        routes_booking_count: &HashMap<(String, String), u64>,
-        n: usize,
-    ) -> Vec<(&(std::string::String, std::string::String), &u64)> {
-        let mut routes_booking_count_vec: Vec<_> = routes_booking_count.iter().collect();
-        routes_booking_count_vec.sort_by(|a, b| b.1.cmp(a.1));
-        routes_booking_count_vec.truncate(n);
-        routes_booking_count_vec
-    }
+    /// Returns the metrics summary, one entry per message.
 
-    fn print_metrics(metrics_lock: &Arc<RwLock<Metrics>>, n: usize) {
+    fn summary(
+        metrics_lock: &Arc<RwLock<Metrics>>,
+        n: usize,
+        inventories: &Inventories,
+        webservices: &[WebService],
+    ) -> Vec<String> {
         let metrics = metrics_lock
             .read()
             .expect("[CRITICAL] Printer could not read metrics lock");
 
-        let n_reqs = metrics.n_reqs;
-        let routes_by_bookings =
-            MetricsCollector::get_n_most_booked_routes(&metrics.routes_booking_count, n);
-
-        let mut most_booked_routes_msg: String = format!(
-            "{:=^36}\n|{:^4}|{:^9}|{:^9}|{:^9}|\n{:=^36}",
-            "", "Nº", "ORIGIN", "DESTINY", "#", ""
-        );
+        let n_reqs = metrics.n_reqs();
+        let mut summary = vec![
+            format!("Requests successfully processed: {} reqs", n_reqs),
+            format!("Passengers booked: {}", metrics.n_passengers()),
+        ];
 
-        for (i, route) in routes_by_bookings.iter().enumerate() {
-            let ((origin, destiny), amount) = route;
-
-            most_booked_routes_msg += &format!(
-                "\n|{:^4}|{:^9}|{:^9}|{:^9}|",
-                i + 1,
-                origin,
-                destiny,
-                amount
-            );
+        if metrics.n_unfinished() > 0 {
+            summary.push(format!(
+                "Requests unfinished: {} reqs",
+                metrics.n_unfinished()
+            ));
+        }
+        if metrics.n_rejected() > 0 {
+            summary.push(format!("Requests rejected: {} reqs", metrics.n_rejected()));
         }
-        most_booked_routes_msg += &format!("\n{:=^36}", "");
-
-        println!(
-            "[{}] Requests successfully processed: {} reqs",
-            utils::now_h_m_s(),
-            n_reqs
-        );
-
         if n_reqs > 0 {
-            println!(
-                "[{}] Mean time to book: {} ms",
-                utils::now_h_m_s(),
-                metrics.reqs_duration_cumsum / (n_reqs as i64)
-            );
-            println!(
-                "[{}] Most booked routes:\n{}",
-                utils::now_h_m_s(),
-                most_booked_routes_msg
-            );
+            summary.push(format!("Mean time to book: {} ms", metrics.mean_time()));
+            summary.push(format!(
+                "Most booked routes:\n{}",
+                format_most_booked_routes(&metrics.most_booked_routes(n))
+            ));
         };
+        for (airline, inventory) in inventories {
+            let flights = inventory
+                .lock()
+                .expect("[CRITICAL] Printer could not take inventory lock")
+                .remaining();
+            summary.push(format!(
+                "Seats remaining:\n{}",
+                format_inventory(airline, &flights)
+            ));
+        }
+        let webservices_metrics: WebServicesMetrics = webservices
+            .iter()
+            .map(|webservice| (webservice.name.clone(), webservice.metrics()))
+            .collect();
+        summary.push(format!(
+            "Webservices:\n{}",
+            format_webservices_metrics(&webservices_metrics)
+        ));
+        summary
+    }
+
+    fn print_metrics(
+        metrics_lock: &Arc<RwLock<Metrics>>,
+        n: usize,
+        inventories: &Inventories,
+        webservices: &[WebService],
+    ) {
+        for msg in MetricsCollector::summary(metrics_lock, n, inventories, webservices) {
+            println!("[{}] {}", utils::now_h_m_s(), msg);
+        }
     }
 
     fn print_metrics_periodically(
@@ -164,6 +203,8 @@ impl MetricsCollector {
         keep_running_lock: Arc<RwLock<bool>>,
         period: std::time::Duration,
         n_most_booked: usize,
+        inventories: Inventories,
+        webservices: Vec<WebService>,
     ) {
         loop {
             {
@@ -174,10 +215,14 @@ impl MetricsCollector {
                     break;
                 };
             }
-            MetricsCollector::print_metrics(&metrics_lock, n_most_booked);
+            MetricsCollector::print_metrics(
+                &metrics_lock,
+                n_most_booked,
+                &inventories,
+                &webservices,
+            );
             thread::sleep(period);
         }
-        MetricsCollector::print_metrics(&metrics_lock, n_most_booked);
     }
 
     pub fn get_sender(&self) -> MetricsSender {
@@ -186,6 +231,8 @@ impl MetricsCollector {
         }
     }
 
+    /// Join the metrics threads and log the final summary.
+
     pub fn join(self) {
         drop(self.tx);
         self.collector_handler
@@ -201,11 +248,29 @@ impl MetricsCollector {
         self.printer_handler
             .join()
             .expect("[CRITICAL] Error joining metrics printer thread");
+
+        self.logger_sender.send(String::from("-- FINAL METRICS --"));
+        for msg in MetricsCollector::summary(
+            &self.metrics,
+            self.n_most_booked,
+            &self.inventories,
+            &self.webservices,
+        ) {
+            self.logger_sender.send(msg);
+        }
     }
 }
 
 impl MetricsSender {
-    pub fn send(&self, req: Request, duration_ms: i64) {
-        let _ = self.tx.send((req, duration_ms));
+    pub fn send(&self, req: RawRequest, duration_ms: i64) {
+        let _ = self.tx.send(MetricsMessage::Finished((req, duration_ms)));
+    }
+
+    pub fn send_unfinished(&self, req: RawRequest) {
+        let _ = self.tx.send(MetricsMessage::Unfinished(req));
+    }
+
+    pub fn send_rejected(&self, req: RawRequest) {
+        let _ = self.tx.send(MetricsMessage::Rejected(req));
     }
 }
@@ -2,11 +2,16 @@
 pub mod airlines;
 pub mod dispatcher;
 pub mod hotel;
+pub mod input;
 pub mod logger;
 pub mod metrics_collector;
+pub mod rejects;
 pub mod request_handler;
+pub mod results;
+pub mod shutdown;
 
 // Private
 mod fetch;
-mod request;
+mod ticket_limiter;
 mod webservice;
+mod worker_pool;
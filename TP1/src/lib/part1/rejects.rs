@@ -0,0 +1,75 @@
+//! Rejected requests report.
+
+use std::fs::File;
+
+use serde::Serialize;
+
+use crate::part1::logger::LoggerSender;
+
+// TYPES ----------------------------------------------------------------------
+
+/// Input row that could not be dispatched, with the reason why.
+#[derive(Debug, Serialize)]
+pub struct Rejected {
+    pub line: u64,
+    pub reason: String,
+    pub row: String,
+}
+
+/// RejectsWriter writes every rejected row (csv format) to the rejects file.
+/// The file is only created once the first row gets rejected.
+
+pub struct RejectsWriter {
+    path: String,
+    writer: Option<csv::Writer<File>>,
+    count: u64,
+    logger_sender: LoggerSender,
+}
+
+// FUNCTIONS ------------------------------------------------------------------
+
+impl RejectsWriter {
+    pub fn new(path: String, logger_sender: LoggerSender) -> Self {
+        RejectsWriter {
+            path,
+            writer: None,
+            count: 0,
+            logger_sender,
+        }
+    }
+
+    /// Logs the rejected row and appends it to the rejects file.
+
+    pub fn write(&mut self, rejected: Rejected) {
+        self.count += 1;
+        self.logger_sender.send(format!(
+            "[WARNING] Rejected request at line {}: {}",
+            rejected.line, rejected.reason
+        ));
+
+        if let Err(err) = self.append(&rejected) {
+            self.logger_sender.send(format!(
+                "[WARNING] Could not write rejected request at line {} to {}: {}",
+                rejected.line, self.path, err
+            ));
+        }
+    }
+
+    fn append(&mut self, rejected: &Rejected) -> csv::Result<()> {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => self.writer.insert(csv::Writer::from_path(&self.path)?),
+        };
+        writer.serialize(rejected)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
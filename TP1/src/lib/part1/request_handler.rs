@@ -1,126 +1,356 @@
 //! Proxy entity in charge of delegating requests to WebServices.
 
-use std::thread::{self, JoinHandle};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+};
 
-use crate::common::utils::*;
+use crate::common::{
+    config::RequestHandlerConfig,
+    inventory::BookingRejection,
+    request::{format_passengers, format_stops, RawRequest, Route},
+    utils::*,
+};
 use crate::part1::{
-    airlines::{Airline, Airlines},
+    airlines::Airlines,
     hotel::Hotel,
     logger::LoggerSender,
     metrics_collector::MetricsSender,
-    request::Request,
+    results::{Outcome, RequestResult, ResultsSender},
+    webservice::{FetchFailure, WebService},
+    worker_pool::WorkerPool,
 };
 
-/// Invalid request structure
-pub struct InvalidRequest;
+/// Reason why a request can not be handled
+#[derive(Debug)]
+pub enum InvalidRequest {
+    UnknownAirline(String),
+    InvalidItinerary(String),
+    InvalidPassengers(String),
+}
 
 /// RequestHandler is an entity that acts as a middleware between the distpatcher
-/// and the webservices. It's in charge of spawning a thread for each incomming
-/// request and holding those references for latter joinning. Some side functionalities
-/// include logging and metrics collecting.
+/// and the webservices. It's in charge of queueing a job for each webservice fetch
+/// of every incomming request into a bounded WorkerPool. Some side functionalities
+/// include logging, metrics collecting and reporting each request result.
 
 pub struct RequestHandler {
     logger_sender: LoggerSender,
     metrics_sender: MetricsSender,
-    threads: Vec<JoinHandle<()>>,
+    results_sender: Option<ResultsSender>,
+    pool: WorkerPool,
     next_id: u32,
     airlines: Airlines,
     hotel: Hotel,
 }
 
-fn handler(
+/// Request being processed. Each of its fetches (one per itinerary leg, plus the hotel) runs as a separate job,
+/// the last one to finish reports the request as finished (rejected if any of
+/// its bookings was rejected, failed if any of its fetches panicked, or cancelled if any of
+/// its fetches was cancelled by a shutdown). The bookings already made of a rejected or
+/// failed request are released.
+
+struct InFlightRequest {
     req_id: u32,
-    airline: Airline,
-    mut hotel: Option<Hotel>,
-    req: Request,
+    line: u64,
+    req: RawRequest,
+    ts_start: i64,
+    ts_fetch_start: AtomicI64,
+    pending_fetches: AtomicUsize,
+    airline_retries: AtomicU32,
+    hotel_retries: AtomicU32,
+    cancelled: AtomicBool,
+    failed: AtomicBool,
+    rejection: Mutex<Option<BookingRejection>>,
+    booked: Mutex<Vec<(WebService, Route)>>,
     logger_sender: LoggerSender,
     metrics_sender: MetricsSender,
-) {
-    let ts_start = now();
-    logger_sender.send(format!("[REQ #{}] -- START --", req_id));
+    results_sender: Option<ResultsSender>,
+}
 
-    let hotel_thread: Option<JoinHandle<u32>> = hotel
-        .take()
-        .map(|hotel| thread::spawn(move || hotel.fetch_with_retries(req_id)));
+/// Guard that counts a fetch of the request as done when dropped, so the last one
+/// finishes the request even if it panicked.
 
-    let retries = airline.fetch_with_retries(req_id);
+struct FetchDone<'a>(&'a InFlightRequest);
 
-    if let Some(join_handler) = hotel_thread {
-        join_handler
-            .join()
-            .expect("[CRITICAL] Error while joining hotel thread.");
+impl Drop for FetchDone<'_> {
+    fn drop(&mut self) {
+        let in_flight = self.0;
+        if thread::panicking() {
+            in_flight.failed.store(true, Ordering::SeqCst);
+        }
+        if in_flight.pending_fetches.fetch_sub(1, Ordering::SeqCst) == 1 {
+            in_flight.finish();
+        }
+    }
+}
+
+impl InFlightRequest {
+    fn fetch(&self, webservice: &WebService, route: &Route, retries: &AtomicU32) {
+        let _done = FetchDone(self);
+
+        // Only the first fetch to start sets when the request left the queue
+        let _ = self
+            .ts_fetch_start
+            .compare_exchange(0, now(), Ordering::SeqCst, Ordering::SeqCst);
+
+        // Retries are added up across the legs of an itinerary
+        match webservice.fetch_with_retries(self.req_id, route, self.req.party_size()) {
+            Ok(n_retries) => {
+                retries.fetch_add(n_retries, Ordering::SeqCst);
+                self.booked
+                    .lock()
+                    .expect("[CRITICAL] Could not take request bookings lock")
+                    .push((webservice.clone(), route.clone()));
+            }
+            Err(FetchFailure::Cancelled { retries: n_retries }) => {
+                retries.fetch_add(n_retries, Ordering::SeqCst);
+                self.cancelled.store(true, Ordering::SeqCst);
+            }
+            Err(FetchFailure::Rejected {
+                retries: n_retries,
+                reason,
+            }) => {
+                retries.fetch_add(n_retries, Ordering::SeqCst);
+                self.lock_rejection().get_or_insert(reason);
+            }
+        }
     }
 
-    let ts_stop = now();
-    let duration_ms = ts_stop - ts_start;
-    logger_sender.send(format!(
-        "[REQ #{}] -- FINISHED -- (time: {} ms, retries: {})",
-        req_id, duration_ms, retries
-    ));
-    metrics_sender.send(req, duration_ms);
+    fn finish(&self) {
+        let ts_stop = now();
+        let duration_ms = ts_stop - self.ts_start;
+        let airline_retries = self.airline_retries.load(Ordering::SeqCst);
+        let hotel_retries = self.hotel_retries.load(Ordering::SeqCst);
+        let ts_fetch_start = self.ts_fetch_start.load(Ordering::SeqCst);
+        let rejection = self.lock_rejection().take();
+        let failed = self.failed.load(Ordering::SeqCst);
+        let outcome = match (&rejection, failed, self.cancelled.load(Ordering::SeqCst)) {
+            (Some(_), _, _) => Outcome::Rejected,
+            (None, true, _) => Outcome::Failed,
+            (None, false, true) => Outcome::Cancelled,
+            (None, false, false) => Outcome::Booked,
+        };
+        let reason = match (&rejection, failed) {
+            (Some(reason), _) => Some(reason.to_string()),
+            (None, true) => Some(String::from("a fetch failed unexpectedly")),
+            (None, false) => None,
+        };
+
+        match outcome {
+            Outcome::Booked => {
+                self.logger_sender.send(format!(
+                    "[REQ #{}] -- FINISHED -- (time: {} ms, airline retries: {}, hotel retries: {})",
+                    self.req_id, duration_ms, airline_retries, hotel_retries
+                ));
+                self.metrics_sender.send(self.req.clone(), duration_ms);
+            }
+            Outcome::Cancelled => {
+                self.logger_sender.send(format!(
+                    "[REQ #{}] -- CANCELLED -- (time: {} ms, airline retries: {}, hotel retries: {})",
+                    self.req_id, duration_ms, airline_retries, hotel_retries
+                ));
+                self.metrics_sender.send_unfinished(self.req.clone());
+            }
+            Outcome::Rejected => {
+                self.logger_sender.send(format!(
+                    "[REQ #{}] -- REJECTED -- (time: {} ms, reason: {})",
+                    self.req_id,
+                    duration_ms,
+                    reason.as_deref().unwrap_or_default()
+                ));
+                self.metrics_sender.send_rejected(self.req.clone());
+                self.release_booked();
+            }
+            Outcome::Failed => {
+                self.logger_sender.send(format!(
+                    "[REQ #{}] -- FAILED -- (time: {} ms, reason: {})",
+                    self.req_id,
+                    duration_ms,
+                    reason.as_deref().unwrap_or_default()
+                ));
+                self.metrics_sender.send_unfinished(self.req.clone());
+                self.release_booked();
+            }
+        }
+
+        if let Some(results_sender) = &self.results_sender {
+            let RawRequest {
+                origin,
+                destiny,
+                airline,
+                package,
+                stops,
+                round_trip,
+                departure_date,
+                return_date,
+                return_airline,
+                passengers,
+                offer_id: _,
+                callback_url: _,
+            } = self.req.clone();
+
+            results_sender.send(RequestResult {
+                line: self.line,
+                req_id: self.req_id,
+                origin,
+                destiny,
+                airline,
+                stops: format_stops(&stops),
+                package,
+                round_trip,
+                departure_date,
+                return_date,
+                return_airline,
+                passengers: format_passengers(&passengers),
+                airline_retries,
+                hotel_retries,
+                queue_wait_ms: ts_fetch_start - self.ts_start,
+                fetch_ms: ts_stop - ts_fetch_start,
+                total_ms: duration_ms,
+                outcome,
+                reason,
+            });
+        }
+    }
+
+    /// Releases the seats of every booking made, as the request was rejected.
+
+    fn release_booked(&self) {
+        let booked = self
+            .booked
+            .lock()
+            .expect("[CRITICAL] Could not take request bookings lock")
+            .split_off(0);
+        for (webservice, route) in booked {
+            webservice.release(self.req_id, &route, self.req.party_size());
+        }
+    }
+
+    fn lock_rejection(&self) -> MutexGuard<'_, Option<BookingRejection>> {
+        self.rejection
+            .lock()
+            .expect("[CRITICAL] Could not take request rejection lock")
+    }
 }
 
 impl RequestHandler {
-    /// Given a Hash of Airlines' Webservices, a Hotel Webservice, a LoggerSender and a MetricsSender
-    /// this function resturns a new instance of a RequestHandler.
+    /// Given a RequestHandlerConfig, a Hash of Airlines' Webservices, a Hotel Webservice,
+    /// a LoggerSender, a MetricsSender and an optional ResultsSender this function resturns
+    /// a new instance of a RequestHandler.
     pub fn new(
+        RequestHandlerConfig {
+            pool_size,
+            queue_size,
+        }: RequestHandlerConfig,
         airlines: Airlines,
         hotel: Hotel,
         logger_sender: LoggerSender,
         metrics_sender: MetricsSender,
+        results_sender: Option<ResultsSender>,
     ) -> Self {
         RequestHandler {
             logger_sender,
             metrics_sender,
-            threads: Vec::new(),
+            results_sender,
+            pool: WorkerPool::new(pool_size, queue_size),
             next_id: 0,
             airlines,
             hotel,
         }
     }
-    /// This method spawns a thread for each incomming requests and delegates to the
-    /// corresponding WebService(s).
 
-    pub fn handle(&mut self, req: Request) -> Result<(), InvalidRequest> {
-        let airline = self.airlines.get(&req.airline).ok_or(InvalidRequest)?;
+    /// This method queues a job for each webservice the incomming request (read from
+    /// the given input line) has to fetch: the airline of each itinerary leg (both ways
+    /// for round trips) and, for packages, the hotel. Blocks while the pool queue is full.
 
-        let airline_cln = airline.clone();
-        let hotel_cln = match req.package {
+    pub fn handle(&mut self, line: u64, req: RawRequest) -> Result<(), InvalidRequest> {
+        req.validate_itinerary()
+            .map_err(InvalidRequest::InvalidItinerary)?;
+        req.validate_passengers()
+            .map_err(InvalidRequest::InvalidPassengers)?;
+        let legs = req
+            .legs()
+            .into_iter()
+            .map(|leg| match self.airlines.get(&leg.airline) {
+                Some(airline) => Ok((airline.clone(), leg.route)),
+                None => Err(InvalidRequest::UnknownAirline(leg.airline)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let hotel = match req.package {
             true => Some(self.hotel.clone()),
             false => None,
         };
 
         let req_id = self.next_id;
-        let logger_sender = self.logger_sender.clone();
-        let metrics_sender = self.metrics_sender.clone();
-        let join_handler = thread::spawn(move || {
-            handler(
-                req_id,
-                airline_cln,
-                hotel_cln,
-                req,
-                logger_sender,
-                metrics_sender,
-            )
-        });
-        self.threads.push(join_handler);
         self.next_id += 1;
+        self.logger_sender
+            .send(format!("[REQ #{}] -- START --", req_id));
+        if let Some(stay) = req.hotel_stay() {
+            self.logger_sender.send(format!(
+                "[REQ #{}] Hotel stay from {} to {} ({} nights)",
+                req_id, stay.check_in, stay.check_out, stay.nights
+            ));
+        }
+
+        let route = req.route();
+        let in_flight = Arc::new(InFlightRequest {
+            req_id,
+            line,
+            req,
+            ts_start: now(),
+            ts_fetch_start: AtomicI64::new(0),
+            pending_fetches: AtomicUsize::new(legs.len() + hotel.is_some() as usize),
+            airline_retries: AtomicU32::new(0),
+            hotel_retries: AtomicU32::new(0),
+            cancelled: AtomicBool::new(false),
+            failed: AtomicBool::new(false),
+            rejection: Mutex::new(None),
+            booked: Mutex::new(Vec::new()),
+            logger_sender: self.logger_sender.clone(),
+            metrics_sender: self.metrics_sender.clone(),
+            results_sender: self.results_sender.clone(),
+        });
+
+        if let Some(hotel) = hotel {
+            let in_flight = in_flight.clone();
+            self.pool
+                .execute(move || in_flight.fetch(&hotel, &route, &in_flight.hotel_retries));
+        }
+        for (airline, route) in legs {
+            let in_flight = in_flight.clone();
+            self.pool
+                .execute(move || in_flight.fetch(&airline, &route, &in_flight.airline_retries));
+        }
 
         Ok(())
     }
 
-    /// Join all thread requests.
+    /// Wait for every queued request and join the pool workers.
 
-    pub fn join(mut self) {
-        for join_handler in self.threads {
-            if let Err(err) = join_handler.join() {
-                self.logger_sender.send(format!(
-                    "[WARNING] Error while joining RequestHandler. Error: {:?}",
-                    err
-                ))
-            }
+    pub fn join(self) {
+        let panicked = self.pool.join();
+        if panicked > 0 {
+            self.logger_sender.send(format!(
+                "[WARNING] {} RequestHandler jobs panicked while processing requests",
+                panicked
+            ));
         }
+    }
+}
 
-        self.threads = Vec::new();
+impl fmt::Display for InvalidRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidRequest::UnknownAirline(airline) => write!(f, "unknown airline '{}'", airline),
+            InvalidRequest::InvalidItinerary(reason) => write!(f, "invalid itinerary ({})", reason),
+            InvalidRequest::InvalidPassengers(reason) => {
+                write!(f, "invalid passengers ({})", reason)
+            }
+        }
     }
 }
@@ -0,0 +1,151 @@
+//! Per-request results report.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread::{spawn, JoinHandle},
+};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+// TYPES ----------------------------------------------------------------------
+
+/// Final outcome of a request.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Outcome {
+    Booked,
+    /// Some of its fetches were cancelled by a shutdown.
+    Cancelled,
+    /// Some of its bookings was definitively rejected (e.g. sold out flight).
+    Rejected,
+    /// Some of its fetches failed unexpectedly (the job panicked), so its bookings were released.
+    Failed,
+}
+
+/// Result of a processed request, one per input row. Multi-leg itineraries list
+/// their `stops` (and `passengers`) as in the input csv, and add up the retries of
+/// every leg (of both ways for round trips).
+/// Times are in ms: `queue_wait_ms` is the time spent waiting for a worker,
+/// `fetch_ms` the time spent fetching the webservices (including their retries)
+/// and `total_ms` the sum of both. `reason` explains a rejection.
+#[derive(Debug, Serialize)]
+pub struct RequestResult {
+    pub line: u64,
+    pub req_id: u32,
+    pub origin: String,
+    pub destiny: String,
+    pub airline: String,
+    pub stops: String,
+    pub package: bool,
+    pub round_trip: bool,
+    pub departure_date: Option<NaiveDate>,
+    pub return_date: Option<NaiveDate>,
+    pub return_airline: Option<String>,
+    pub passengers: String,
+    pub airline_retries: u32,
+    pub hotel_retries: u32,
+    pub queue_wait_ms: i64,
+    pub fetch_ms: i64,
+    pub total_ms: i64,
+    pub outcome: Outcome,
+    pub reason: Option<String>,
+}
+
+enum Format {
+    Csv(Box<csv::Writer<File>>),
+    JsonLines(BufWriter<File>),
+}
+
+/// ResultsWriter is an entity that keeps a reference to the thread
+/// that writes the results file. A channel is used for communication.
+/// Files ending in `.jsonl` or `.json` are written as JSON lines, any other one as csv.
+
+pub struct ResultsWriter {
+    handler: JoinHandle<()>,
+    tx: Sender<RequestResult>,
+}
+
+/// ResultsSender holds a reference to the channel that handles the communication
+/// with the results writer thread.
+
+#[derive(Clone)]
+pub struct ResultsSender {
+    tx: Sender<RequestResult>,
+}
+
+// FUNCTIONS ------------------------------------------------------------------
+
+impl ResultsWriter {
+    /// Given a path this method will create the results file and spawn the writer thread.
+
+    pub fn from_path(path: &str) -> Result<ResultsWriter, Box<dyn Error>> {
+        let file = File::create(path)?;
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("");
+        let format = match extension {
+            "jsonl" | "json" => Format::JsonLines(BufWriter::new(file)),
+            _ => Format::Csv(Box::new(csv::Writer::from_writer(file))),
+        };
+
+        let (tx, rx): (Sender<RequestResult>, Receiver<RequestResult>) = channel();
+        let handler = spawn(move || ResultsWriter::write_results(rx, format));
+
+        Ok(ResultsWriter { handler, tx })
+    }
+
+    fn write_results(rx: Receiver<RequestResult>, mut format: Format) {
+        // Each result is flushed right away, so the file can be inspected while running
+        while let Ok(result) = rx.recv() {
+            let written = match &mut format {
+                Format::Csv(writer) => writer
+                    .serialize(&result)
+                    .and_then(|_| writer.flush().map_err(csv::Error::from))
+                    .map_err(|err| err.to_string()),
+                Format::JsonLines(writer) => serde_json::to_string(&result)
+                    .map_err(|err| err.to_string())
+                    .and_then(|line| {
+                        writeln!(writer, "{}", line)
+                            .and_then(|_| writer.flush())
+                            .map_err(|err| err.to_string())
+                    }),
+            };
+
+            if let Err(err) = written {
+                println!(
+                    "[WARNING] Could not write result of request #{}: {}",
+                    result.req_id, err
+                );
+            }
+        }
+    }
+
+    /// Get Sender copy for ResultsWriter communication channel.
+
+    pub fn get_sender(&self) -> ResultsSender {
+        ResultsSender {
+            tx: self.tx.clone(),
+        }
+    }
+
+    /// Join thread responsible for writing the results.
+
+    pub fn join(self) {
+        drop(self.tx);
+        self.handler
+            .join()
+            .expect("[CRITICAL] Error joining results writer thread");
+    }
+}
+
+impl ResultsSender {
+    pub fn send(&self, result: RequestResult) {
+        let _ = self.tx.send(result);
+    }
+}
@@ -0,0 +1,116 @@
+//! Shutdown flag, set on SIGINT/SIGTERM.
+
+use std::{
+    error::Error,
+    process,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// Exit code used when a second signal forces the exit.
+const FORCED_EXIT_CODE: i32 = 130;
+
+/// Shutdown is a flag shared by every entity that has to stop early when the
+/// process is asked to terminate. Sleeping through it (`sleep`) wakes up as soon
+/// as the shutdown is requested.
+
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    state: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Shutdown::default()
+    }
+
+    /// Installs the SIGINT/SIGTERM handler that requests the shutdown.
+    /// A second signal exits right away. The handler lives as long as the
+    /// process, so it must not hold any sender (the Logger would never be joined).
+
+    pub fn install_handler(&self) -> Result<(), Box<dyn Error>> {
+        let shutdown = self.clone();
+        ctrlc::set_handler(move || {
+            if shutdown.is_requested() {
+                println!("[WARNING] Forced exit, unfinished requests are lost");
+                process::exit(FORCED_EXIT_CODE);
+            }
+            shutdown.request();
+        })?;
+        Ok(())
+    }
+
+    pub fn request(&self) {
+        *self.lock() = true;
+        self.state.1.notify_all();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.lock()
+    }
+
+    /// Sleeps `secs` seconds or until the shutdown is requested.
+    /// Returns whether the whole time was slept.
+
+    pub fn sleep(&self, secs: u64) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(secs);
+        let mut requested = self.lock();
+
+        while !*requested {
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            requested = self
+                .state
+                .1
+                .wait_timeout(requested, deadline - now)
+                .expect("[CRITICAL] Could not wait on shutdown flag")
+                .0;
+        }
+        false
+    }
+
+    fn lock(&self) -> MutexGuard<'_, bool> {
+        self.state
+            .0
+            .lock()
+            .expect("[CRITICAL] Could not take shutdown lock")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn sleep_returns_once_the_shutdown_is_requested() {
+        let shutdown = Shutdown::new();
+        let requester = shutdown.clone();
+        let start = Instant::now();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            requester.request();
+        });
+
+        assert!(!shutdown.sleep(3600));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        handle.join().expect("requester panicked");
+
+        // Once requested, sleeping does not wait at all
+        let start = Instant::now();
+        assert!(!shutdown.sleep(3600));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn sleep_lasts_the_whole_time_without_a_shutdown() {
+        let shutdown = Shutdown::new();
+        let start = Instant::now();
+        assert!(shutdown.sleep(1));
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert!(!shutdown.is_requested());
+    }
+}
@@ -0,0 +1,151 @@
+//! FIFO-fair rate limiter.
+
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+struct TicketState {
+    next_ticket: u64,
+    next_to_admit: u64,
+    in_use: usize,
+}
+
+/// TicketLimiter lets at most `limit` threads in at the same time, admitting
+/// them in arrival order: each thread takes a ticket and waits until it is its
+/// turn and there is a free slot, so no thread can be overtaken by later ones.
+
+pub struct TicketLimiter {
+    state: Mutex<TicketState>,
+    turn: Condvar,
+    limit: usize,
+}
+
+/// Slot taken from a TicketLimiter, released when dropped.
+pub struct TicketGuard<'a> {
+    limiter: &'a TicketLimiter,
+}
+
+impl TicketLimiter {
+    pub fn new(limit: usize) -> Self {
+        TicketLimiter {
+            state: Mutex::new(TicketState {
+                next_ticket: 0,
+                next_to_admit: 0,
+                in_use: 0,
+            }),
+            turn: Condvar::new(),
+            limit: limit.max(1),
+        }
+    }
+
+    /// Blocks until every thread that arrived earlier got in and there is a free slot.
+
+    pub fn access(&self) -> TicketGuard<'_> {
+        let mut state = self.lock();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+
+        while ticket != state.next_to_admit || state.in_use >= self.limit {
+            state = self
+                .turn
+                .wait(state)
+                .expect("[CRITICAL] Could not wait on rate limiter");
+        }
+        state.next_to_admit += 1;
+        state.in_use += 1;
+        // The next ticket may fit in a free slot too
+        self.turn.notify_all();
+
+        TicketGuard { limiter: self }
+    }
+
+    fn release(&self) {
+        self.lock().in_use -= 1;
+        self.turn.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TicketState> {
+        self.state
+            .lock()
+            .expect("[CRITICAL] Could not take rate limiter lock")
+    }
+
+    #[cfg(test)]
+    fn waiting(&self) -> u64 {
+        let state = self.lock();
+        state.next_ticket - state.next_to_admit
+    }
+}
+
+impl Drop for TicketGuard<'_> {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use super::TicketLimiter;
+
+    fn wait_until_waiting(limiter: &TicketLimiter, n: u64) {
+        while limiter.waiting() < n {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn admits_in_arrival_order() {
+        let limiter = Arc::new(TicketLimiter::new(1));
+        let admitted = Arc::new(Mutex::new(Vec::new()));
+        let guard = limiter.access();
+
+        let waiters: Vec<_> = (0..8)
+            .map(|i| {
+                let (waiter_limiter, admitted) = (limiter.clone(), admitted.clone());
+                let waiter = thread::spawn(move || {
+                    let _guard = waiter_limiter.access();
+                    admitted.lock().unwrap().push(i);
+                });
+                wait_until_waiting(&limiter, i + 1);
+                waiter
+            })
+            .collect();
+
+        drop(guard);
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(*admitted.lock().unwrap(), (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn waiting_thread_is_not_overtaken_by_later_arrivals() {
+        let limiter = Arc::new(TicketLimiter::new(2));
+        let victim_admitted = Arc::new(AtomicBool::new(false));
+        let guards = (limiter.access(), limiter.access());
+
+        let victim = {
+            let (limiter, victim_admitted) = (limiter.clone(), victim_admitted.clone());
+            thread::spawn(move || {
+                let _guard = limiter.access();
+                victim_admitted.store(true, Ordering::SeqCst);
+            })
+        };
+        wait_until_waiting(&limiter, 1);
+
+        // A greedy thread releasing and taking the slot again must queue behind the victim
+        drop(guards.0);
+        let _guard = limiter.access();
+        assert!(victim_admitted.load(Ordering::SeqCst));
+
+        drop(guards.1);
+        victim.join().unwrap();
+    }
+}
@@ -1,65 +1,198 @@
-use std::sync::Arc;
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 
-use std_semaphore::Semaphore;
+use crate::common::{
+    config::WebServiceConfig,
+    inventory::{BookingRejection, SeatInventory},
+    metrics::WebServiceMetrics,
+    request::Route,
+    simulation::Simulation,
+};
+use crate::part1::{
+    fetch::*, logger::LoggerSender, shutdown::Shutdown, ticket_limiter::TicketLimiter,
+};
 
-use crate::common::utils::*;
-use crate::part1::{fetch::*, logger::LoggerSender};
+pub type SharedInventory = Arc<Mutex<SeatInventory>>;
+
+/// Reason why a fetch with retries did not succeed, after `retries` retries.
+pub enum FetchFailure {
+    /// Interrupted by a shutdown.
+    Cancelled { retries: u32 },
+    /// Definitively rejected by the webservice.
+    Rejected {
+        retries: u32,
+        reason: BookingRejection,
+    },
+}
 
 #[derive(Clone)]
 pub struct WebService {
     pub name: String,
-    sem: Arc<Semaphore>,
-    failure_rate: f64,
+    limiter: Arc<TicketLimiter>,
     retry_time: u64,
     logger_sender: LoggerSender,
-    min_delay: u64,
-    max_delay: u64,
+    simulation: Arc<Simulation>,
+    inventory: SharedInventory,
+    metrics: Arc<Mutex<WebServiceMetrics>>,
+    shutdown: Shutdown,
 }
 
 impl WebService {
-    pub fn new(
-        name: String,
-        rate_limit: isize,
-        failure_rate: f64,
-        retry_time: u64,
+    pub fn from_config(
+        config: WebServiceConfig,
+        seed: Option<u64>,
         logger_sender: LoggerSender,
-        min_delay: u64,
-        max_delay: u64,
+        shutdown: Shutdown,
     ) -> Self {
         WebService {
-            name,
-            sem: Arc::new(Semaphore::new(rate_limit)),
-            failure_rate,
-            retry_time,
+            simulation: Arc::new(Simulation::from_config(&config, seed)),
+            inventory: Arc::new(Mutex::new(SeatInventory::from_config(&config.flights))),
+            name: config.name,
+            limiter: Arc::new(TicketLimiter::new(config.rate_limit as usize)),
+            retry_time: config.retry_time,
+            metrics: Arc::new(Mutex::new(WebServiceMetrics::default())),
             logger_sender,
-            min_delay,
-            max_delay,
+            shutdown,
         }
     }
 
-    pub fn fetch(&self, req_id: u32) -> Result<(), FetchError> {
-        let _guard = self.sem.access();
+    /// Returns the seat inventory, only if its seats are limited.
+
+    pub fn inventory(&self) -> Option<SharedInventory> {
+        let limited = self
+            .inventory
+            .lock()
+            .expect("[CRITICAL] Could not take webservice inventory lock")
+            .is_limited();
+
+        match limited {
+            true => Some(self.inventory.clone()),
+            false => None,
+        }
+    }
+
+    /// Returns a snapshot of the webservice operational metrics.
+
+    pub fn metrics(&self) -> WebServiceMetrics {
+        self.metrics
+            .lock()
+            .expect("[CRITICAL] Could not take webservice metrics lock")
+            .clone()
+    }
+
+    fn update_metrics(&self, update: impl FnOnce(&mut WebServiceMetrics)) {
+        update(
+            &mut self
+                .metrics
+                .lock()
+                .expect("[CRITICAL] Could not take webservice metrics lock"),
+        );
+    }
+
+    /// Fetches the webservice once, booking `seats` seats for the given route.
+
+    fn fetch(
+        &self,
+        simulation: &mut Simulation,
+        req_id: u32,
+        route: &Route,
+        seats: u32,
+    ) -> Result<(), FetchError> {
+        self.update_metrics(|metrics| metrics.queued += 1);
+        let _guard = self.limiter.access();
+        self.update_metrics(|metrics| {
+            metrics.queued -= 1;
+            metrics.in_flight += 1;
+        });
         self.logger_sender
             .send(format!("[REQ #{}] Fetching {}...", req_id, self.name));
-        simulate_fetch(self.failure_rate, self.min_delay, self.max_delay)
+        let fetched = simulate_fetch(simulation, &self.shutdown);
+        self.update_metrics(|metrics| {
+            metrics.in_flight -= 1;
+            match &fetched {
+                Err(FetchError::Cancelled) => (),
+                Err(FetchError::Failed) => {
+                    metrics.attempts += 1;
+                    metrics.failures += 1;
+                }
+                _ => metrics.attempts += 1,
+            }
+        });
+        fetched?;
+
+        self.inventory
+            .lock()
+            .expect("[CRITICAL] Could not take webservice inventory lock")
+            .book(route, seats)
+            .map_err(FetchError::Rejected)
     }
 
-    pub fn fetch_with_retries(&self, req_id: u32) -> u32 {
+    /// Releases the `seats` seats booked for the given route, once its request was rejected.
+
+    pub fn release(&self, req_id: u32, route: &Route, seats: u32) {
+        let mut inventory = self
+            .inventory
+            .lock()
+            .expect("[CRITICAL] Could not take webservice inventory lock");
+        if inventory.is_limited() {
+            inventory.release(route, seats);
+            self.logger_sender.send(format!(
+                "[REQ #{}] Released {} seats of {} -> {} in {}",
+                req_id, seats, route.origin, route.destiny, self.name
+            ));
+        }
+    }
+
+    /// Fetches the webservice until it succeeds, returning the amount of retries.
+    /// Stops retrying as soon as the shutdown is requested or the booking is rejected.
+
+    pub fn fetch_with_retries(
+        &self,
+        req_id: u32,
+        route: &Route,
+        seats: u32,
+    ) -> Result<u32, FetchFailure> {
         let mut retries: u32 = 0;
+        let mut simulation = self
+            .simulation
+            .for_booking(&format!("{}:{}:{}", req_id, route.origin, route.destiny));
 
         self.logger_sender.send(format!(
             "[REQ #{}] Waiting to fetch {}...",
             req_id, self.name
         ));
         loop {
-            if let Ok(()) = self.fetch(req_id) {
-                return retries;
+            if self.shutdown.is_requested() {
+                return Err(self.cancel(req_id, retries));
+            }
+            match self.fetch(&mut simulation, req_id, route, seats) {
+                Ok(()) => return Ok(retries),
+                Err(FetchError::Cancelled) => return Err(self.cancel(req_id, retries)),
+                Err(FetchError::Rejected(reason)) => {
+                    self.logger_sender.send(format!(
+                        "[REQ #{}] Fetch to {} rejected: {}",
+                        req_id, self.name, reason
+                    ));
+                    return Err(FetchFailure::Rejected { retries, reason });
+                }
+                Err(FetchError::Failed) => (),
             };
             self.logger_sender.send(format!(
                 "[REQ #{}] Fetch to {} failed! Retrying in {} secs.",
                 req_id, self.name, self.retry_time
             ));
-            sleep(self.retry_time);
+            let retry_start = Instant::now();
+            self.update_metrics(|metrics| metrics.retrying += 1);
+            let slept = self.shutdown.sleep(self.retry_time);
+            self.update_metrics(|metrics| {
+                metrics.retrying -= 1;
+                metrics.retry_wait_ms += retry_start.elapsed().as_millis() as u64;
+            });
+            if !slept {
+                return Err(self.cancel(req_id, retries));
+            }
             retries += 1;
             self.logger_sender.send(format!(
                 "[REQ #{}] Waiting to fetch {}... (retries: {})",
@@ -67,4 +200,89 @@ impl WebService {
             ));
         }
     }
+
+    fn cancel(&self, req_id: u32, retries: u32) -> FetchFailure {
+        self.logger_sender.send(format!(
+            "[REQ #{}] Fetch to {} cancelled (retries: {})",
+            req_id, self.name, retries
+        ));
+        FetchFailure::Cancelled { retries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process, thread, time::Duration};
+
+    use super::*;
+    use crate::common::{config::LoggerConfig, utils::uuid};
+    use crate::part1::logger::Logger;
+
+    /// Returns a webservice whose fetches answer right away and always fail, retried after an hour.
+    fn failing_webservice(logger: &Logger, shutdown: &Shutdown) -> WebService {
+        let config = WebServiceConfig {
+            name: String::from("Iberia"),
+            rate_limit: 1,
+            failure_rate: 1.0,
+            retry_time: 3600,
+            min_delay: 0,
+            max_delay: 1,
+            flights: None,
+        };
+        WebService::from_config(config, Some(1), logger.get_sender(), shutdown.clone())
+    }
+
+    fn start_logger() -> (Logger, String) {
+        let dirpath = env::temp_dir()
+            .join(format!("tp1-webservice-{}-{}", process::id(), uuid()))
+            .to_string_lossy()
+            .to_string();
+        let logger = Logger::from_config(LoggerConfig {
+            dirpath: dirpath.clone(),
+        })
+        .expect("Could not start logger");
+        (logger, dirpath)
+    }
+
+    fn route() -> Route {
+        Route {
+            origin: String::from("EZE"),
+            destiny: String::from("MAD"),
+        }
+    }
+
+    #[test]
+    fn shutdown_cancels_the_retry_without_fetching_again() {
+        let (logger, dirpath) = start_logger();
+        let shutdown = Shutdown::new();
+        let webservice = failing_webservice(&logger, &shutdown);
+
+        let fetcher = webservice.clone();
+        let handle = thread::spawn(move || fetcher.fetch_with_retries(1, &route(), 1));
+        while webservice.metrics().retrying == 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        shutdown.request();
+
+        match handle.join().expect("fetch panicked") {
+            Err(FetchFailure::Cancelled { retries }) => assert_eq!(retries, 0),
+            _ => panic!("fetch not cancelled"),
+        }
+        let metrics = webservice.metrics();
+        assert_eq!(
+            (metrics.attempts, metrics.failures, metrics.retrying),
+            (1, 1, 0)
+        );
+
+        // Once requested, nothing is fetched at all
+        match webservice.fetch_with_retries(2, &route(), 1) {
+            Err(FetchFailure::Cancelled { retries }) => assert_eq!(retries, 0),
+            _ => panic!("fetch not cancelled"),
+        }
+        assert_eq!(webservice.metrics().attempts, 1);
+
+        drop(webservice);
+        logger.join();
+        std::fs::remove_dir_all(dirpath).ok();
+    }
 }
@@ -0,0 +1,196 @@
+//! Bounded pool of worker threads.
+
+use std::{
+    collections::VecDeque,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+struct QueueState {
+    jobs: VecDeque<Job>,
+    closed: bool,
+}
+
+/// Job queue shared by the pool and its workers. Producers block while it is
+/// full (`not_full`) and workers block while it is empty (`not_empty`).
+
+struct JobQueue {
+    state: Mutex<QueueState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    panicked: AtomicUsize,
+}
+
+/// WorkerPool is an entity that keeps a fixed amount of threads running jobs
+/// from a bounded queue, so the amount of threads does not grow with the
+/// amount of requests.
+
+pub struct WorkerPool {
+    workers: Vec<JoinHandle<()>>,
+    queue: Arc<JobQueue>,
+}
+
+impl JobQueue {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state
+            .lock()
+            .expect("[CRITICAL] Could not take worker pool queue lock")
+    }
+
+    fn push(&self, job: Job) {
+        let mut state = self.lock();
+        while state.jobs.len() >= self.capacity {
+            state = self
+                .not_full
+                .wait(state)
+                .expect("[CRITICAL] Could not wait on worker pool queue");
+        }
+        state.jobs.push_back(job);
+        self.not_empty.notify_one();
+    }
+
+    /// Blocks until there is a job to run, or returns None when the queue is closed and empty.
+
+    fn pop(&self) -> Option<Job> {
+        let mut state = self.lock();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                self.not_full.notify_one();
+                return Some(job);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self
+                .not_empty
+                .wait(state)
+                .expect("[CRITICAL] Could not wait on worker pool queue");
+        }
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.not_empty.notify_all();
+    }
+}
+
+impl WorkerPool {
+    /// Given a pool size and a queue capacity this method spawns the workers.
+
+    pub fn new(size: usize, capacity: usize) -> Self {
+        let queue = Arc::new(JobQueue {
+            state: Mutex::new(QueueState {
+                jobs: VecDeque::new(),
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: capacity.max(1),
+            panicked: AtomicUsize::new(0),
+        });
+
+        let workers = (0..size.max(1))
+            .map(|_| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    // A panicking job must not take its worker down with it
+                    while let Some(job) = queue.pop() {
+                        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                            queue.panicked.fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        WorkerPool { workers, queue }
+    }
+
+    /// Queues a job to be run by some worker. Blocks while the queue is full.
+
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.queue.push(Box::new(job));
+    }
+
+    /// Waits for every queued job to finish and joins the workers.
+    /// Returns the amount of jobs that panicked.
+
+    pub fn join(self) -> usize {
+        self.queue.close();
+        for worker in self.workers {
+            if worker.join().is_err() {
+                self.queue.panicked.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        self.queue.panicked.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc::{self, RecvTimeoutError},
+        time::Duration,
+    };
+
+    use super::*;
+
+    const WAIT: Duration = Duration::from_millis(200);
+
+    #[test]
+    fn execute_blocks_while_the_queue_is_full() {
+        let pool = WorkerPool::new(1, 1);
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel();
+
+        // The only worker is busy and the queue has room for a single job
+        pool.execute(move || {
+            started_tx.send(()).expect("test ended");
+            release_rx.recv().expect("test ended");
+        });
+        started_rx
+            .recv_timeout(WAIT)
+            .expect("first job not started");
+        let done = done_tx.clone();
+        pool.execute(move || done.send(2).expect("test ended"));
+
+        thread::scope(|scope| {
+            let queued = scope.spawn(|| pool.execute(move || done_tx.send(3).expect("test ended")));
+            thread::sleep(WAIT);
+            assert!(!queued.is_finished(), "job queued over the capacity");
+
+            release_tx.send(()).expect("first job ended");
+            queued.join().expect("execute panicked");
+        });
+        assert_eq!(done_rx.recv_timeout(WAIT), Ok(2));
+        assert_eq!(done_rx.recv_timeout(WAIT), Ok(3));
+        assert_eq!(pool.join(), 0);
+    }
+
+    #[test]
+    fn a_panicking_job_does_not_stop_its_worker() {
+        let pool = WorkerPool::new(1, 4);
+        let (done_tx, done_rx) = mpsc::channel();
+
+        pool.execute(|| panic!("job panicked"));
+        pool.execute(move || done_tx.send(()).expect("test ended"));
+
+        assert_eq!(done_rx.recv_timeout(WAIT), Ok(()));
+        assert_eq!(
+            done_rx.recv_timeout(WAIT),
+            Err(RecvTimeoutError::Disconnected)
+        );
+        assert_eq!(pool.join(), 1);
+    }
+}
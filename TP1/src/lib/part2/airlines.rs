@@ -1,11 +1,12 @@
-use std::{collections::HashMap, error::Error};
+use std::collections::HashMap;
 
-use actix::{Actor, Addr};
+use actix::{Addr, Supervisor};
 
-use crate::common::{config::AirlineConfig, config::AirlinesConfig};
+use crate::common::{config::AirlinesConfig, inventory::SeatInventory, simulation::Simulation};
 use crate::part2::{
     dispatcher::{WebServiceDispatcher, WebServiceType},
     logger::Logger,
+    metrics::MetricsCollector,
     status_service::StatusService,
     webservice::WebService,
 };
@@ -17,45 +18,37 @@ pub type Airlines = HashMap<String, Airline>;
 
 // FUNCTIONS ------------------------------------------------------------------
 
-pub fn from_path(
-    path: &str,
+pub fn from_config(
+    airlines: AirlinesConfig,
+    seed: Option<u64>,
     logger: Addr<Logger>,
+    metrics_collector: Addr<MetricsCollector>,
     status_service: Addr<StatusService>,
-) -> Result<Airlines, Box<dyn Error>> {
+) -> Airlines {
     let mut content = Airlines::new();
 
-    let data = std::fs::read_to_string(path)?;
-    let airlines: AirlinesConfig = serde_json::from_str(&data)?;
-
-    for AirlineConfig {
-        name,
-        rate_limit,
-        failure_rate,
-        retry_time,
-        min_delay,
-        max_delay,
-    } in airlines
-    {
+    for config in airlines {
         let airline = WebService::new(
-            name.clone(),
-            failure_rate,
-            min_delay,
-            max_delay,
+            config.name.clone(),
+            Simulation::from_config(&config, seed),
+            SeatInventory::from_config(&config.flights),
             logger.clone(),
-        )
-        .start();
+            metrics_collector.clone(),
+        );
+        let airline = Supervisor::start(|_| airline);
         let dispatcher = WebServiceDispatcher::new(
             airline,
-            name.clone(),
-            rate_limit,
-            retry_time,
+            config.name.clone(),
+            config.rate_limit,
+            config.retry_time,
             logger.clone(),
             status_service.clone(),
+            metrics_collector.clone(),
             WebServiceType::Airline,
-        )
-        .start();
-        content.insert(name, dispatcher);
+        );
+        let dispatcher = Supervisor::start(|_| dispatcher);
+        content.insert(config.name, dispatcher);
     }
 
-    Ok(content)
+    content
 }
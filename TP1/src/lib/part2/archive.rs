@@ -0,0 +1,174 @@
+//! Rolling archive for evicted request statuses.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::common::config::ArchiveConfig;
+use crate::part2::status_service::RequestStatus;
+
+// CONSTANTS ------------------------------------------------------------------
+
+const ARCHIVE_PREFIX: &str = "status-archive";
+const ARCHIVE_EXTENSION: &str = "jsonl";
+
+// ENTITY ---------------------------------------------------------------------
+
+/// StatusArchive keeps evicted request statuses on disk, one JSON per line.
+/// When the current file exceeds `max_file_size` it is rotated, keeping
+/// at most `max_files` files (the oldest one gets discarded).
+/// Every archived status is indexed by request id, with the generation of its file
+/// (increased on every rotation) and its offset, so it is read without scanning.
+
+pub struct StatusArchive {
+    dirpath: String,
+    max_file_size: u64,
+    max_files: usize,
+    file: File,
+    current_size: u64,
+    generation: u64,
+    index: HashMap<String, (u64, u64)>,
+}
+
+impl StatusArchive {
+    /// Given an ArchiveConfig this method will create the archive directory
+    /// and open (or create) the current archive file.
+
+    pub fn new(
+        ArchiveConfig {
+            dirpath,
+            max_file_size,
+            max_files,
+        }: ArchiveConfig,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(&dirpath)?;
+        let file = StatusArchive::open(&StatusArchive::file_path(&dirpath, 0))?;
+        let current_size = file.metadata()?.len();
+
+        let mut archive = StatusArchive {
+            dirpath,
+            max_file_size,
+            max_files: max_files.max(1),
+            file,
+            current_size,
+            generation: max_files.max(1) as u64,
+            index: HashMap::new(),
+        };
+        archive.build_index()?;
+        Ok(archive)
+    }
+
+    /// Indexes the files left by a previous run, from the oldest to the newest one.
+
+    fn build_index(&mut self) -> io::Result<()> {
+        for index in (0..self.max_files).rev() {
+            let file = match File::open(StatusArchive::file_path(&self.dirpath, index)) {
+                Ok(file) => file,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            let generation = self.generation - index as u64;
+
+            let mut reader = BufReader::new(file);
+            let mut offset = 0;
+            let mut line = String::new();
+            loop {
+                line.clear();
+                let len = reader.read_line(&mut line)? as u64;
+                if len == 0 {
+                    break;
+                }
+                // Newest entries are appended last, so the last one wins
+                if let Ok(req_status) = serde_json::from_str::<RequestStatus>(&line) {
+                    self.index.insert(req_status.req.id, (generation, offset));
+                }
+                offset += len;
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends a request status to the archive, rotating files if needed.
+
+    pub fn append(&mut self, req_status: &RequestStatus) -> io::Result<()> {
+        let mut line = serde_json::to_string(req_status)?;
+        line.push('\n');
+
+        if self.current_size > 0 && self.current_size + line.len() as u64 > self.max_file_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.index.insert(
+            req_status.req.id.clone(),
+            (self.generation, self.current_size),
+        );
+        self.current_size += line.len() as u64;
+
+        Ok(())
+    }
+
+    /// Whether the status of a request is archived.
+
+    pub fn contains(&self, req_id: &str) -> bool {
+        self.index.contains_key(req_id)
+    }
+
+    /// Looks up a request status by id, reading its line from the indexed file and offset.
+
+    pub fn lookup(&self, req_id: &str) -> io::Result<Option<RequestStatus>> {
+        let (generation, offset) = match self.index.get(req_id) {
+            Some(entry) => *entry,
+            None => return Ok(None),
+        };
+        let index = (self.generation - generation) as usize;
+
+        let mut file = File::open(StatusArchive::file_path(&self.dirpath, index))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut line = String::new();
+        BufReader::new(file).read_line(&mut line)?;
+
+        Ok(Some(serde_json::from_str(&line)?))
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for index in (0..self.max_files - 1).rev() {
+            let from = StatusArchive::file_path(&self.dirpath, index);
+            if from.exists() {
+                fs::rename(from, StatusArchive::file_path(&self.dirpath, index + 1))?;
+            }
+        }
+
+        let current = StatusArchive::file_path(&self.dirpath, 0);
+        if current.exists() {
+            // Only possible when max_files is 1
+            fs::remove_file(&current)?;
+        }
+
+        self.file = StatusArchive::open(&current)?;
+        self.current_size = 0;
+
+        // Entries of the discarded file are no longer archived
+        self.generation += 1;
+        let oldest = self.generation + 1 - self.max_files as u64;
+        self.index
+            .retain(|_, (generation, _)| *generation >= oldest);
+
+        Ok(())
+    }
+
+    fn open(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn file_path(dirpath: &str, index: usize) -> PathBuf {
+        let filename = match index {
+            0 => format!("{}.{}", ARCHIVE_PREFIX, ARCHIVE_EXTENSION),
+            n => format!("{}.{}.{}", ARCHIVE_PREFIX, n, ARCHIVE_EXTENSION),
+        };
+        PathBuf::from(dirpath).join(filename)
+    }
+}
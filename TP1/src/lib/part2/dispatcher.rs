@@ -1,16 +1,23 @@
 use std::collections::VecDeque;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use actix::{
     clock::sleep, Actor, ActorFutureExt, Addr, AsyncContext, Context, Handler, Message,
-    ResponseActFuture, WrapFuture,
+    MessageResponse, ResponseActFuture, Supervised, WrapFuture,
 };
+use actix_web::rt::task::JoinHandle;
+use serde::Serialize;
 
+use crate::common::metrics::WebServiceMetrics;
 use crate::part2::{
+    health::{Ping, Pong},
     logger::Logger,
-    request::Request,
-    status_service::{BookSucceeded, StatusService},
-    webservice::{Book, WebService},
+    metrics::MetricsCollector,
+    request::Booking,
+    status_service::{
+        BookRejected, BookSucceeded, EventKind, RecordEvent, RequestEvent, StatusService,
+    },
+    webservice::{Book, Release, WebService},
 };
 
 // TYPES ----------------------------------------------------------------------
@@ -21,20 +28,42 @@ pub enum WebServiceType {
     Hotel,
 }
 
+/// State of a dispatcher after pausing or resuming it. `queue_depth` are the bookings
+/// still waiting (held, if paused) and `in_flight` the fetches in progress.
+#[derive(Debug, MessageResponse, Serialize)]
+pub struct DispatcherState {
+    pub webservice: String,
+    pub paused: bool,
+    pub queue_depth: usize,
+    pub in_flight: u64,
+}
+
 // ACTOR ----------------------------------------------------------------------
 
+/// WebServiceDispatcher is an entity <Actor>. It books through its WebService at most
+/// `rate_limit` bookings at a time, queueing the rest in `pending_reqs`. While `paused`
+/// every booking is queued (held) without calling the WebService, until it is resumed.
+/// Events and results are reported to the StatusService in order, through `last_report`.
+/// It runs supervised: if it is restarted, the bookings waiting to be retried are queued again.
+
 pub struct WebServiceDispatcher {
     name: String,
     rate_limit: isize,
-    pending_reqs: VecDeque<Request>,
+    paused: bool,
+    pending_reqs: VecDeque<Booking>,
+    retrying: Vec<Booking>,
     retry_time: u64,
     service: Addr<WebService>,
     logger: Addr<Logger>,
     status_service: Addr<StatusService>,
+    last_report: Option<JoinHandle<()>>,
+    metrics_collector: Addr<MetricsCollector>,
+    metrics: WebServiceMetrics,
     webservice_type: WebServiceType,
 }
 
 impl WebServiceDispatcher {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         service: Addr<WebService>,
         name: String,
@@ -42,46 +71,230 @@ impl WebServiceDispatcher {
         retry_time: u64,
         logger: Addr<Logger>,
         status_service: Addr<StatusService>,
+        metrics_collector: Addr<MetricsCollector>,
         webservice_type: WebServiceType,
     ) -> Self {
         WebServiceDispatcher {
             name,
+            paused: false,
             pending_reqs: VecDeque::new(),
+            retrying: Vec::new(),
             rate_limit,
             retry_time,
             service,
             logger,
             status_service,
+            last_report: None,
+            metrics_collector,
+            metrics: WebServiceMetrics::default(),
             webservice_type,
         }
     }
 
-    fn book(&mut self, req: Request, addr: Addr<WebServiceDispatcher>) {
+    /// Reports the dispatcher operational metrics to the MetricsCollector.
+
+    fn report_metrics(&mut self) {
+        self.metrics.queued = self.pending_reqs.len() as u64;
+        MetricsCollector::update_webservice(
+            &self.metrics_collector,
+            self.name.clone(),
+            self.metrics.clone(),
+        );
+    }
+
+    /// Event of the booking, to be recorded in the timeline of its request.
+
+    fn event(&self, booking: &Booking, kind: EventKind, detail: Option<String>) -> RecordEvent {
+        RecordEvent {
+            req_id: booking.req.id.clone(),
+            event: RequestEvent {
+                webservice: Some(self.name.clone()),
+                leg: booking.leg,
+                detail,
+                ..RequestEvent::new(kind)
+            },
+        }
+    }
+
+    /// Records an event of the booking in the timeline of its request.
+
+    fn record_event(&mut self, booking: &Booking, kind: EventKind, detail: Option<String>) {
+        let event = self.event(booking, kind, detail);
+        self.report(event);
+    }
+
+    /// Registers a finished fetch, releasing its slot.
+
+    fn fetch_finished(&mut self, failed: bool) {
+        self.metrics.in_flight -= 1;
+        self.metrics.attempts += 1;
+        if failed {
+            self.metrics.failures += 1;
+        }
+    }
+
+    /// Reports an event or the result of a booking to the StatusService. The report waits for
+    /// room in its mailbox, outside of this actor, so it is not lost if the dispatcher restarts,
+    /// and is sent after the previous one, so the timeline of a leg ends with its result.
+
+    fn report<M>(&mut self, msg: M)
+    where
+        M: Message<Result = ()> + Send + 'static,
+        StatusService: Handler<M>,
+    {
+        let previous = self.last_report.take();
+        let status_service = self.status_service.clone();
+        let logger = self.logger.clone();
+        let name = self.name.clone();
+        self.last_report = Some(actix::spawn(async move {
+            if let Some(previous) = previous {
+                previous.await.ok();
+            }
+            if let Err(err) = status_service.send(msg).await {
+                Logger::send_to(
+                    &logger,
+                    format!(
+                        "({}) [WARNING] Could not report to StatusService: {}",
+                        name, err
+                    ),
+                );
+            }
+        }));
+    }
+
+    /// Books (or queues) a booking, whether it is new or retried.
+
+    fn dispatch(&mut self, booking: Booking, ctx: &mut Context<Self>) {
+        if self.paused {
+            Logger::send_to(
+                &self.logger,
+                format!("({}) Holding request {}", self.name, booking.req),
+            );
+            self.record_event(&booking, EventKind::Held, None);
+            self.pending_reqs.push_back(booking);
+        } else if self.rate_limit > 0 {
+            self.rate_limit -= 1;
+            self.book(booking, ctx);
+        } else {
+            Logger::send_to(
+                &self.logger,
+                format!("({}) Queueing request {}", self.name, booking.req),
+            );
+            self.record_event(&booking, EventKind::Queued, None);
+            self.pending_reqs.push_back(booking);
+        }
+        self.report_metrics();
+    }
+
+    /// Sends the booking to the WebService, in a slot already taken. If the WebService can't
+    /// take it (its mailbox is full or closed) the booking is queued again at the front, its
+    /// slot is released and the queue is drained after `retry_time` secs.
+    /// Returns whether the booking was sent.
+
+    fn book(&mut self, booking: Booking, ctx: &mut Context<Self>) -> bool {
+        self.metrics.in_flight += 1;
         Logger::send_to(
             &self.logger,
-            format!("({}) Fetching for request {}", self.name, req.id),
+            format!("({}) Fetching for request {}", self.name, booking.req),
         );
-        self.service
-            .try_send(Book {
-                req,
-                requester: addr,
-            })
-            .expect("[CRITICAL] Error while fetching web service")
+        self.record_event(&booking, EventKind::FetchStarted, None);
+
+        let msg = Book {
+            booking,
+            requester: ctx.address(),
+        };
+        match self.service.try_send(msg) {
+            Ok(()) => true,
+            Err(err) => {
+                let reason = err.to_string();
+                let Book { booking, .. } = err.into_inner();
+                Logger::send_to(
+                    &self.logger,
+                    format!(
+                        "({}) [WARNING] WebService unavailable ({}), queueing again request {}",
+                        self.name, reason, booking.req
+                    ),
+                );
+                self.record_event(&booking, EventKind::Queued, Some(reason));
+                self.metrics.in_flight -= 1;
+                self.rate_limit += 1;
+                self.pending_reqs.push_front(booking);
+                ctx.run_later(Duration::from_secs(self.retry_time), |me, ctx| {
+                    if !me.paused {
+                        me.drain(ctx);
+                        me.report_metrics();
+                    }
+                });
+                false
+            }
+        }
     }
 
-    fn book_or_release(&mut self, addr: Addr<WebServiceDispatcher>) {
-        match self.pending_reqs.pop_front() {
-            Some(next_req) => self.book(next_req, addr),
+    fn book_or_release(&mut self, ctx: &mut Context<Self>) {
+        // While paused the slot is released, to be taken again on resume
+        let next_req = match self.paused {
+            true => None,
+            false => self.pending_reqs.pop_front(),
+        };
+        match next_req {
+            Some(next_req) => {
+                self.book(next_req, ctx);
+            }
             None => self.rate_limit += 1,
         }
+        self.report_metrics();
+    }
+
+    /// Books the pending requests while there are free slots (and the WebService takes them).
+
+    fn drain(&mut self, ctx: &mut Context<Self>) {
+        while self.rate_limit > 0 {
+            let next_req = match self.pending_reqs.pop_front() {
+                Some(next_req) => next_req,
+                None => break,
+            };
+            self.rate_limit -= 1;
+            if !self.book(next_req, ctx) {
+                break;
+            }
+        }
+    }
+
+    fn state(&self) -> DispatcherState {
+        DispatcherState {
+            webservice: self.name.clone(),
+            paused: self.paused,
+            queue_depth: self.pending_reqs.len(),
+            in_flight: self.metrics.in_flight,
+        }
     }
 }
 
 impl Actor for WebServiceDispatcher {
     type Context = Context<Self>;
 
-    fn started(&mut self, _: &mut Self::Context) {
+    fn started(&mut self, ctx: &mut Self::Context) {
         Logger::send_to(&self.logger, format!("({}) Dispatcher started", self.name));
+        if !self.paused {
+            self.drain(ctx);
+        }
+        self.report_metrics();
+    }
+}
+
+impl Supervised for WebServiceDispatcher {
+    fn restarting(&mut self, _ctx: &mut Context<Self>) {
+        Logger::send_to(
+            &self.logger,
+            format!(
+                "({}) Dispatcher restarting, queueing again {} requests waiting to be retried",
+                self.name,
+                self.retrying.len()
+            ),
+        );
+        // Scheduled retries were dropped along with the context, they are booked once started
+        self.metrics.retrying -= self.retrying.len() as u64;
+        self.pending_reqs.extend(self.retrying.drain(..));
     }
 }
 
@@ -90,19 +303,41 @@ impl Actor for WebServiceDispatcher {
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct HandleBook {
-    pub req: Request,
+    pub booking: Booking,
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct FetchSucceeded {
-    pub req: Request,
+    pub booking: Booking,
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct FetchFailed {
-    pub req: Request,
+    pub booking: Booking,
+}
+
+/// Message to release a booking made, as its request was rejected.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ReleaseBooking {
+    pub booking: Booking,
+}
+
+/// Message to pause (hold every booking) or resume the dispatcher.
+#[derive(Message)]
+#[rtype(result = "DispatcherState")]
+pub struct SetPaused {
+    pub paused: bool,
+}
+
+/// Message that indicates the webservice definitively rejected the booking (no retry).
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct FetchRejected {
+    pub booking: Booking,
+    pub reason: String,
 }
 
 // HANDLERS -------------------------------------------------------------------
@@ -113,19 +348,9 @@ impl Handler<HandleBook> for WebServiceDispatcher {
     fn handle(&mut self, msg: HandleBook, ctx: &mut Context<Self>) {
         Logger::send_to(
             &self.logger,
-            format!("({}) HandleBook for request {}", self.name, msg.req.id),
+            format!("({}) HandleBook for request {}", self.name, msg.booking.req),
         );
-
-        if self.rate_limit > 0 {
-            self.book(msg.req, ctx.address());
-            self.rate_limit -= 1;
-        } else {
-            Logger::send_to(
-                &self.logger,
-                format!("({}) Queueing request {}", self.name, msg.req.id),
-            );
-            self.pending_reqs.push_back(msg.req);
-        }
+        self.dispatch(msg.booking, ctx);
     }
 }
 
@@ -135,15 +360,44 @@ impl Handler<FetchSucceeded> for WebServiceDispatcher {
     fn handle(&mut self, msg: FetchSucceeded, ctx: &mut Context<Self>) {
         Logger::send_to(
             &self.logger,
-            format!("({}) FetchSucceeded for request {}", self.name, msg.req.id),
+            format!(
+                "({}) FetchSucceeded for request {}",
+                self.name, msg.booking.req
+            ),
         );
-        self.status_service
-            .try_send(BookSucceeded {
-                req: msg.req,
-                book_type: self.webservice_type,
-            })
-            .expect("[CRITICAL] BookSucceeded sending failed");
-        self.book_or_release(ctx.address());
+        self.record_event(&msg.booking, EventKind::Booked, None);
+        self.report(BookSucceeded {
+            booking: msg.booking,
+            book_type: self.webservice_type,
+        });
+        self.fetch_finished(false);
+        self.book_or_release(ctx);
+    }
+}
+
+impl Handler<FetchRejected> for WebServiceDispatcher {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        FetchRejected { booking, reason }: FetchRejected,
+        ctx: &mut Context<Self>,
+    ) {
+        Logger::send_to(
+            &self.logger,
+            format!(
+                "({}) FetchRejected for request {}: {}",
+                self.name, booking.req, reason
+            ),
+        );
+        self.record_event(&booking, EventKind::Rejected, Some(reason.clone()));
+        self.report(BookRejected {
+            booking,
+            book_type: self.webservice_type,
+            reason,
+        });
+        self.fetch_finished(false);
+        self.book_or_release(ctx);
     }
 }
 
@@ -153,26 +407,229 @@ impl Handler<FetchFailed> for WebServiceDispatcher {
     fn handle(&mut self, msg: FetchFailed, ctx: &mut Context<Self>) -> Self::Result {
         Logger::send_to(
             &self.logger,
-            format!("({}) FetchFailed for request {}", self.name, msg.req.id),
+            format!(
+                "({}) FetchFailed for request {}",
+                self.name, msg.booking.req
+            ),
         );
-        self.book_or_release(ctx.address());
+        self.record_event(&msg.booking, EventKind::FetchFailed, None);
+        self.fetch_finished(true);
+        self.metrics.retrying += 1;
+        self.book_or_release(ctx);
 
         // We wait retry_time until retrying the failed req
         Logger::send_to(
             &self.logger,
             format!(
                 "({}) Waiting {} secs before retrying for request {}",
-                self.name, self.retry_time, msg.req.id
+                self.name, self.retry_time, msg.booking.req
             ),
         );
+        self.record_event(
+            &msg.booking,
+            EventKind::RetryScheduled,
+            Some(format!("retrying in {} secs", self.retry_time)),
+        );
+        self.retrying.push(msg.booking.clone());
+        let retry_start = Instant::now();
         Box::pin(
             sleep(Duration::from_secs(self.retry_time))
                 .into_actor(self)
-                .map(move |_result, _me, ctx| {
-                    ctx.address()
-                        .try_send(HandleBook { req: msg.req })
-                        .expect("[CRITICAL] Could not send HandleBook msg to dispatcher");
+                .map(move |_result, me, ctx| {
+                    me.metrics.retrying -= 1;
+                    me.metrics.retry_wait_ms += retry_start.elapsed().as_millis() as u64;
+                    me.retrying
+                        .retain(|retrying| !retrying.is_same(&msg.booking));
+                    me.dispatch(msg.booking, ctx);
                 }),
         )
     }
 }
+
+impl Handler<ReleaseBooking> for WebServiceDispatcher {
+    type Result = ();
+
+    fn handle(&mut self, ReleaseBooking { booking }: ReleaseBooking, _ctx: &mut Context<Self>) {
+        Logger::send_to(
+            &self.logger,
+            format!(
+                "({}) Releasing booking of request {}",
+                self.name, booking.req
+            ),
+        );
+        // Waits for room in the WebService mailbox, as a lost release leaks its seats
+        let service = self.service.clone();
+        let logger = self.logger.clone();
+        let name = self.name.clone();
+        actix::spawn(async move {
+            if let Err(err) = service.send(Release { booking }).await {
+                Logger::send_to(
+                    &logger,
+                    format!(
+                        "({}) [WARNING] Could not release booking in WebService: {}",
+                        name, err
+                    ),
+                );
+            }
+        });
+    }
+}
+
+impl Handler<SetPaused> for WebServiceDispatcher {
+    type Result = DispatcherState;
+
+    fn handle(&mut self, SetPaused { paused }: SetPaused, ctx: &mut Context<Self>) -> Self::Result {
+        if self.paused == paused {
+            return self.state();
+        }
+        self.paused = paused;
+
+        let (action, kind) = match paused {
+            true => ("paused", EventKind::Held),
+            false => ("resumed", EventKind::Released),
+        };
+        Logger::send_to(
+            &self.logger,
+            format!(
+                "({}) Dispatcher {} with {} pending requests",
+                self.name,
+                action,
+                self.pending_reqs.len()
+            ),
+        );
+        let events: Vec<RecordEvent> = self
+            .pending_reqs
+            .iter()
+            .map(|booking| self.event(booking, kind, None))
+            .collect();
+        for event in events {
+            self.report(event);
+        }
+        if !paused {
+            self.drain(ctx);
+        }
+        self.report_metrics();
+
+        self.state()
+    }
+}
+
+impl Handler<Ping> for WebServiceDispatcher {
+    type Result = Pong;
+
+    fn handle(&mut self, _msg: Ping, _ctx: &mut Context<Self>) -> Self::Result {
+        Pong {
+            queue_depth: Some(self.pending_reqs.len()),
+            paused: Some(self.paused),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix::{ActorContext, Supervisor};
+
+    use super::*;
+    use crate::common::{
+        config::{StoreBackend, WebServiceConfig},
+        inventory::SeatInventory,
+        simulation::Simulation,
+    };
+    use crate::part2::request::Request;
+    use crate::part2::status_service::{
+        tests::{get_status, request, start_logger_and_metrics, start_status_service, temp_dir},
+        NewRequest,
+    };
+
+    /// Message that stops the dispatcher, as a failure would, so it is restarted.
+    #[derive(Message)]
+    #[rtype(result = "()")]
+    struct Crash;
+
+    impl Handler<Crash> for WebServiceDispatcher {
+        type Result = ();
+
+        fn handle(&mut self, _msg: Crash, ctx: &mut Context<Self>) {
+            ctx.stop();
+        }
+    }
+
+    #[actix_web::test]
+    async fn restarted_dispatcher_completes_queued_bookings() {
+        let dirpath = temp_dir();
+        let status_service = start_status_service(&dirpath, StoreBackend::Memory);
+        let (logger, metrics_collector) = start_logger_and_metrics(&dirpath);
+        let config = WebServiceConfig {
+            name: String::from("Iberia"),
+            rate_limit: 1,
+            failure_rate: 0.0,
+            retry_time: 3600,
+            min_delay: 0,
+            max_delay: 1,
+            flights: None,
+        };
+        let service = WebService::new(
+            config.name.clone(),
+            Simulation::from_config(&config, None),
+            SeatInventory::from_config(&None),
+            logger.clone(),
+            metrics_collector.clone(),
+        );
+        let service = Supervisor::start(|_| service);
+        let mut dispatcher = WebServiceDispatcher::new(
+            service,
+            config.name.clone(),
+            config.rate_limit,
+            config.retry_time,
+            logger,
+            status_service.clone(),
+            metrics_collector,
+            WebServiceType::Airline,
+        );
+
+        let (a, b) = (request("a"), request("b"));
+        for req in [&a, &b] {
+            status_service
+                .send(NewRequest { req: req.clone() })
+                .await
+                .expect("StatusService unavailable");
+        }
+        let booking = |req: &Request, leg: usize| Booking {
+            req: req.clone(),
+            leg: Some(leg),
+        };
+
+        // One booking waits an hour to be retried and the others are held while paused
+        dispatcher.retrying.push(booking(&b, 1));
+        dispatcher.metrics.retrying = 1;
+        let dispatcher = Supervisor::start(|_| dispatcher);
+        let set_paused = |paused: bool| dispatcher.send(SetPaused { paused });
+        set_paused(true).await.expect("Dispatcher unavailable");
+        for booking in [booking(&a, 0), booking(&a, 1), booking(&b, 0)] {
+            dispatcher
+                .send(HandleBook { booking })
+                .await
+                .expect("Dispatcher unavailable");
+        }
+
+        // The restart drops the scheduled retry, so its booking is queued again
+        dispatcher
+            .send(Crash)
+            .await
+            .expect("Dispatcher unavailable");
+        let state = set_paused(true).await.expect("Dispatcher not restarted");
+        assert_eq!(state.queue_depth, 4);
+
+        set_paused(false).await.expect("Dispatcher unavailable");
+        for _ in 0..50 {
+            let a = get_status(&status_service, "a").await.expect("a not found");
+            let b = get_status(&status_service, "b").await.expect("b not found");
+            if a.status() == "COMPLETED" && b.status() == "COMPLETED" {
+                std::fs::remove_dir_all(dirpath).ok();
+                return;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        panic!("Queued bookings not completed after the restart");
+    }
+}
@@ -1,12 +1,23 @@
 //! Server output errors.
 
+use crate::common::request::Route;
+
 pub enum HandlerError {
-    AirlineNotFound,
+    InvalidItinerary(String),
+    InvalidPassengers(String),
+    InvalidCallbackUrl(String),
+    AirlineNotFound(String),
     AirlineUnavailable,
     HotelUnavailable,
     StatusServiceUnavailable,
 }
 
+pub enum OfferError {
+    OfferNotFound,
+    OfferExpired,
+    RouteMismatch(Route),
+}
+
 pub enum StatusServiceError {
     RequestNotFound,
 }
@@ -0,0 +1,178 @@
+//! Health checks.
+//!
+//! Every actor answers Ping with its queue depth (if it has one). A component is `up` when it
+//! answers within PING_TIMEOUT, and `down` when its mailbox is closed (the actor died) or it
+//! does not answer in time. Actors are pinged concurrently, so a check answers within
+//! PING_TIMEOUT.
+//! * Liveness checks the core actors: Logger, MetricsCollector, StatusService and RequestHandler.
+//! * Readiness also checks the WebServiceDispatcher of every airline and the hotel.
+
+use std::time::{Duration, Instant};
+
+use actix::{dev::ToEnvelope, Actor, Addr, Handler, Message, MessageResponse};
+use futures::future::{join, join_all};
+use serde::Serialize;
+
+use crate::part2::{request_handler::GetDispatchers, state::ServerState};
+
+// CONSTANTS ------------------------------------------------------------------
+
+/// Time each actor has to answer a Ping.
+pub const PING_TIMEOUT: Duration = Duration::from_millis(1000);
+
+// TYPES ----------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+/// Health of an actor. `queue_depth` is the amount of pending work: bookings waiting
+/// for the rate limit or held while paused (dispatchers), or webhooks being delivered
+/// (StatusService). A paused dispatcher is still `up`, as it keeps accepting bookings.
+#[derive(Debug, Serialize)]
+pub struct ComponentHealth {
+    pub component: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webservice: Option<String>,
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Health of the whole server: `up` only if every component is up.
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    pub status: HealthStatus,
+    pub components: Vec<ComponentHealth>,
+}
+
+// MESSAGES -------------------------------------------------------------------
+
+/// Message to check an actor is alive.
+#[derive(Message)]
+#[rtype(result = "Pong")]
+pub struct Ping;
+
+/// Answer to a Ping: the queue depth of the actor (if it has a queue) and,
+/// for dispatchers, whether they are paused.
+#[derive(Default, MessageResponse)]
+pub struct Pong {
+    pub queue_depth: Option<usize>,
+    pub paused: Option<bool>,
+}
+
+// FUNCTIONS ------------------------------------------------------------------
+
+/// Given the ServerState this function pings the core actors.
+
+pub async fn check_live(state: &ServerState) -> HealthResponse {
+    HealthResponse::new(core_components(state).await)
+}
+
+/// Given the ServerState this function pings the core actors and every dispatcher.
+
+pub async fn check_ready(state: &ServerState) -> HealthResponse {
+    let deadline = Instant::now() + PING_TIMEOUT;
+    let (mut components, dispatchers) = join(
+        core_components(state),
+        dispatcher_components(state, deadline),
+    )
+    .await;
+    components.extend(dispatchers);
+
+    HealthResponse::new(components)
+}
+
+async fn core_components(state: &ServerState) -> Vec<ComponentHealth> {
+    let (logger, metrics_collector, status_service, request_handler) = futures::join!(
+        ping("Logger", &state.logger, PING_TIMEOUT),
+        ping("MetricsCollector", &state.metrics_collector, PING_TIMEOUT),
+        ping("StatusService", &state.status_service, PING_TIMEOUT),
+        ping("RequestHandler", &state.request_handler, PING_TIMEOUT),
+    );
+    vec![logger, metrics_collector, status_service, request_handler]
+}
+
+/// Lists the dispatchers and pings them with the time left until the deadline.
+
+async fn dispatcher_components(state: &ServerState, deadline: Instant) -> Vec<ComponentHealth> {
+    match state
+        .request_handler
+        .send(GetDispatchers)
+        .timeout(PING_TIMEOUT)
+        .await
+    {
+        Ok(dispatchers) => {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            join_all(
+                dispatchers
+                    .into_iter()
+                    .map(|(name, dispatcher)| async move {
+                        let mut health = ping("WebServiceDispatcher", &dispatcher, timeout).await;
+                        health.webservice = Some(name);
+                        health
+                    }),
+            )
+            .await
+        }
+        // Dispatchers can't be reached without the RequestHandler, already reported as down
+        Err(err) => vec![ComponentHealth::down(
+            "WebServiceDispatcher",
+            format!("could not list dispatchers ({})", err),
+        )],
+    }
+}
+
+async fn ping<A>(component: &str, addr: &Addr<A>, timeout: Duration) -> ComponentHealth
+where
+    A: Actor + Handler<Ping>,
+    A::Context: ToEnvelope<A, Ping>,
+{
+    match addr.send(Ping).timeout(timeout).await {
+        Ok(Pong {
+            queue_depth,
+            paused,
+        }) => ComponentHealth {
+            component: component.to_string(),
+            webservice: None,
+            status: HealthStatus::Up,
+            queue_depth,
+            paused,
+            error: None,
+        },
+        Err(err) => ComponentHealth::down(component, err.to_string()),
+    }
+}
+
+impl ComponentHealth {
+    fn down(component: &str, error: String) -> Self {
+        ComponentHealth {
+            component: component.to_string(),
+            webservice: None,
+            status: HealthStatus::Down,
+            queue_depth: None,
+            paused: None,
+            error: Some(error),
+        }
+    }
+}
+
+impl HealthResponse {
+    fn new(components: Vec<ComponentHealth>) -> Self {
+        let status = match components
+            .iter()
+            .all(|component| component.status == HealthStatus::Up)
+        {
+            true => HealthStatus::Up,
+            false => HealthStatus::Down,
+        };
+        HealthResponse { status, components }
+    }
+}
@@ -1,11 +1,10 @@
-use std::error::Error;
+use actix::{Addr, Supervisor};
 
-use actix::{Actor, Addr};
-
-use crate::common::config::HotelConfig;
+use crate::common::{config::HotelConfig, inventory::SeatInventory, simulation::Simulation};
 use crate::part2::{
     dispatcher::{WebServiceDispatcher, WebServiceType},
     logger::Logger,
+    metrics::MetricsCollector,
     status_service::StatusService,
     webservice::WebService,
 };
@@ -16,40 +15,30 @@ pub type Hotel = Addr<WebServiceDispatcher>;
 
 // FUNCTIONS ------------------------------------------------------------------
 
-pub fn from_path(
-    path: &str,
+pub fn from_config(
+    config: HotelConfig,
+    seed: Option<u64>,
     logger: Addr<Logger>,
+    metrics_collector: Addr<MetricsCollector>,
     status_service: Addr<StatusService>,
-) -> Result<Addr<WebServiceDispatcher>, Box<dyn Error>> {
-    let data = std::fs::read_to_string(path)?;
-
-    let HotelConfig {
-        name,
-        rate_limit,
-        failure_rate,
-        retry_time,
-        min_delay,
-        max_delay,
-    } = serde_json::from_str(&data)?;
-
+) -> Hotel {
     let hotel = WebService::new(
-        name.clone(),
-        failure_rate,
-        min_delay,
-        max_delay,
+        config.name.clone(),
+        Simulation::from_config(&config, seed),
+        SeatInventory::from_config(&config.flights),
         logger.clone(),
-    )
-    .start();
+        metrics_collector.clone(),
+    );
+    let hotel = Supervisor::start(|_| hotel);
     let dispatcher = WebServiceDispatcher::new(
         hotel,
-        name,
-        rate_limit,
-        retry_time,
+        config.name,
+        config.rate_limit,
+        config.retry_time,
         logger,
         status_service,
+        metrics_collector,
         WebServiceType::Hotel,
-    )
-    .start();
-
-    Ok(dispatcher)
+    );
+    Supervisor::start(|_| dispatcher)
 }
@@ -8,6 +8,7 @@ use std::{
 use actix::{Actor, Addr, AsyncContext, Context, Handler, Message};
 
 use crate::common::{config::LoggerConfig, utils};
+use crate::part2::health::{Ping, Pong};
 
 // ACTOR ----------------------------------------------------------------------
 
@@ -70,3 +71,11 @@ impl Handler<LogMessage> for Logger {
             .expect("[CRITICAL] Write to file failed");
     }
 }
+
+impl Handler<Ping> for Logger {
+    type Result = Pong;
+
+    fn handle(&mut self, _msg: Ping, _ctx: &mut Context<Self>) -> Self::Result {
+        Pong::default()
+    }
+}
@@ -4,52 +4,39 @@
 //! * Mean request time
 //! * Top n routes
 //! * Number of requests
+//! * Number of rejected requests
+//! * Remaining seats of each airline with declared flights
+//! * Queued and in-flight fetches, attempts, failures and retry wait time of each webservice
 
-use std::collections::HashMap;
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
-use actix::{
-    clock::sleep, Actor, ActorFutureExt, Addr, AsyncContext, Context, Handler, Message,
-    ResponseActFuture, WrapFuture,
-};
+use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, MessageResult, Supervised};
 use actix_web::Result;
 use serde::Serialize;
 
-use crate::common::config::MetricsCollectorConfig;
-use crate::part2::logger::Logger;
-
-// TYPES ----------------------------------------------------------------------
-
-/// Struct that is used as hash index to keep track of metrics for that route.
-/// It has origin and destiny for request petition.
-#[derive(Serialize, Clone, PartialEq, Eq, Hash)]
-pub struct Route {
-    origin: String,
-    destiny: String,
-}
-
-/// Struct that is used to output top booked airline metrics.
-#[derive(Serialize)]
-pub struct RouteMetrics {
-    route: Route,
-    amount: u64,
-}
-
-pub type MostBookedRoutes = Vec<RouteMetrics>;
+use crate::common::{
+    config::MetricsCollectorConfig,
+    inventory::{format_inventory, FlightInventory},
+    metrics::{
+        format_most_booked_routes, format_webservices_metrics, Metrics, MostBookedRoutes,
+        WebServiceMetrics, WebServicesMetrics,
+    },
+    request::Route,
+};
+use crate::part2::{
+    health::{Ping, Pong},
+    logger::Logger,
+};
 
 // ACTOR ----------------------------------------------------------------------
 
-struct Metrics {
-    routes_booking_count: HashMap<Route, u64>,
-    reqs_duration_cumsum: i64,
-    n_reqs: u64,
-}
-
 /// MetricsCollector is an entity <Actor>. It will be in charge of collecting
 /// metrics using MetricsMessage. It will also log periodically to stdout metrics status using inner calls.
 
 pub struct MetricsCollector {
     metrics: Metrics,
+    inventories: BTreeMap<String, Vec<FlightInventory>>,
+    webservices: WebServicesMetrics,
     printer_period: u64,
     n_most_booked: usize,
     logger_addr: Addr<Logger>,
@@ -66,32 +53,31 @@ impl MetricsCollector {
         logger_addr: Addr<Logger>,
     ) -> Self {
         MetricsCollector {
-            metrics: Metrics {
-                routes_booking_count: HashMap::new(),
-                reqs_duration_cumsum: 0,
-                n_reqs: 0,
-            },
+            metrics: Metrics::new(),
+            inventories: BTreeMap::new(),
+            webservices: WebServicesMetrics::new(),
             printer_period,
             n_most_booked,
             logger_addr,
         }
     }
 
-    /// Given a MetricsCollector addr this method is used to send the actor current petition metrics.
+    /// Given a MetricsCollector addr this method is used to send the actor current petition metrics,
+    /// with the routes booked by the petition and its amount of passengers.
 
     pub fn collect(
         metrics_collector: &Addr<MetricsCollector>,
         start_time: i64,
         end_time: i64,
-        origin: String,
-        destiny: String,
+        routes: Vec<Route>,
+        passengers: u32,
     ) {
         if metrics_collector
             .try_send(MetricsMessage {
                 start_time,
                 end_time,
-                origin,
-                destiny,
+                routes,
+                passengers,
             })
             .is_err()
         {
@@ -99,68 +85,91 @@ impl MetricsCollector {
         };
     }
 
-    fn get_n_most_booked_routes(&self) -> MostBookedRoutes {
-        let routes_booking_count = &self.metrics.routes_booking_count;
-        let n = self.n_most_booked;
-
-        let mut routes_booking_count_vec: Vec<(&Route, &u64)> =
-            routes_booking_count.iter().collect();
-        routes_booking_count_vec.sort_by(|a, b| b.1.cmp(a.1));
-        routes_booking_count_vec.truncate(n);
-
-        routes_booking_count_vec
-            .into_iter()
-            .map(|(route, amount)| RouteMetrics {
-                route: route.clone(),
-                amount: *amount,
-            })
-            .collect()
+    /// Given a MetricsCollector addr this method is used to report a definitively rejected petition.
+
+    pub fn collect_rejected(metrics_collector: &Addr<MetricsCollector>) {
+        if metrics_collector.try_send(RequestRejected {}).is_err() {
+            println!("Warning: failed to send metrics to RequestRejected");
+        };
     }
 
-    fn log_metrics(&self) {
-        let n_reqs = self.metrics.n_reqs;
-        let most_booked_routes = self.get_n_most_booked_routes();
+    /// Given a MetricsCollector addr this method is used to report the remaining seats of an airline.
 
-        let mut most_booked_routes_msg: String = format!(
-            "{:=^36}\n|{:^4}|{:^9}|{:^9}|{:^9}|\n{:=^36}",
-            "", "Nº", "ORIGIN", "DESTINY", "#", ""
-        );
+    pub fn update_inventory(
+        metrics_collector: &Addr<MetricsCollector>,
+        airline: String,
+        flights: Vec<FlightInventory>,
+    ) {
+        if metrics_collector
+            .try_send(InventoryUpdate { airline, flights })
+            .is_err()
+        {
+            println!("Warning: failed to send metrics to InventoryUpdate");
+        };
+    }
+
+    /// Given a MetricsCollector addr this method is used to report the operational metrics of a webservice.
 
-        for (
-            i,
-            RouteMetrics {
-                route: Route { origin, destiny },
-                amount,
-            },
-        ) in most_booked_routes.iter().enumerate()
+    pub fn update_webservice(
+        metrics_collector: &Addr<MetricsCollector>,
+        name: String,
+        metrics: WebServiceMetrics,
+    ) {
+        if metrics_collector
+            .try_send(WebServiceUpdate { name, metrics })
+            .is_err()
         {
-            most_booked_routes_msg += &format!(
-                "\n|{:^4}|{:^9}|{:^9}|{:^9}|",
-                i + 1,
-                origin,
-                destiny,
-                amount
-            );
-        }
-        most_booked_routes_msg += &format!("\n{:=^36}", "");
+            println!("Warning: failed to send metrics to WebServiceUpdate");
+        };
+    }
+
+    fn get_n_most_booked_routes(&self) -> MostBookedRoutes {
+        self.metrics.most_booked_routes(self.n_most_booked)
+    }
+
+    fn log_metrics(&self) {
+        let n_reqs = self.metrics.n_reqs();
+        let most_booked_routes_msg = format_most_booked_routes(&self.get_n_most_booked_routes());
 
         Logger::send_to(
             &self.logger_addr,
             format!("Requests successfully processed: {} reqs", n_reqs),
         );
+        Logger::send_to(
+            &self.logger_addr,
+            format!("Passengers booked: {}", self.metrics.n_passengers()),
+        );
         if n_reqs > 0 {
             Logger::send_to(
                 &self.logger_addr,
-                format!(
-                    "Mean time to book: {} ms",
-                    self.metrics.reqs_duration_cumsum / (n_reqs as i64)
-                ),
+                format!("Mean time to book: {} ms", self.metrics.mean_time()),
             );
             Logger::send_to(
                 &self.logger_addr,
                 format!("Most booked routes:\n{}", most_booked_routes_msg),
             );
         };
+        if self.metrics.n_rejected() > 0 {
+            Logger::send_to(
+                &self.logger_addr,
+                format!("Requests rejected: {} reqs", self.metrics.n_rejected()),
+            );
+        }
+        for (airline, flights) in &self.inventories {
+            Logger::send_to(
+                &self.logger_addr,
+                format!("Seats remaining:\n{}", format_inventory(airline, flights)),
+            );
+        }
+        if !self.webservices.is_empty() {
+            Logger::send_to(
+                &self.logger_addr,
+                format!(
+                    "Webservices:\n{}",
+                    format_webservices_metrics(&self.webservices)
+                ),
+            );
+        }
     }
 }
 
@@ -169,18 +178,22 @@ impl Actor for MetricsCollector {
 
     fn started(&mut self, ctx: &mut Self::Context) {
         Logger::send_to(&self.logger_addr, "[MetricsCollector] Started".to_string());
-        ctx.address()
-            .try_send(LogMetrics {})
-            .expect("[CRITICAL] Could not auto-send LogMetrics msg to MetricsCollector");
+        ctx.run_interval(Duration::from_millis(self.printer_period), |me, _ctx| {
+            me.log_metrics()
+        });
     }
 }
 
-// MESSAGES -------------------------------------------------------------------
+impl Supervised for MetricsCollector {
+    fn restarting(&mut self, _ctx: &mut Context<Self>) {
+        Logger::send_to(
+            &self.logger_addr,
+            "[MetricsCollector] Restarting".to_string(),
+        );
+    }
+}
 
-/// Message
-#[derive(Message)]
-#[rtype(result = "()")]
-struct LogMetrics;
+// MESSAGES -------------------------------------------------------------------
 
 /// Message to provide Metrics for a petition.
 #[derive(Message)]
@@ -188,8 +201,29 @@ struct LogMetrics;
 pub struct MetricsMessage {
     start_time: i64,
     end_time: i64,
-    origin: String,
-    destiny: String,
+    routes: Vec<Route>,
+    passengers: u32,
+}
+
+/// Message to report a definitively rejected petition.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RequestRejected;
+
+/// Message to report the remaining seats of an airline.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct InventoryUpdate {
+    airline: String,
+    flights: Vec<FlightInventory>,
+}
+
+/// Message to report the operational metrics of a webservice.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct WebServiceUpdate {
+    name: String,
+    metrics: WebServiceMetrics,
 }
 
 /// Response for GetMetrics message. It provides current status of those metrics.
@@ -197,8 +231,12 @@ pub struct MetricsMessage {
 #[rtype(result = "()")]
 pub struct MetricsResponse {
     pub n_req: u64,
+    pub n_passengers: u64,
     pub req_mean_time: i64,
     pub most_booked_routes: MostBookedRoutes,
+    pub n_rejected: u64,
+    pub seats_remaining: BTreeMap<String, Vec<FlightInventory>>,
+    pub webservices: WebServicesMetrics,
 }
 
 /// GetMetrics message to get current status metrics.
@@ -206,27 +244,15 @@ pub struct MetricsResponse {
 #[rtype(result = "Result<MetricsResponse, ()>")]
 pub struct GetMetrics;
 
-// HANDLERS -------------------------------------------------------------------
-
-impl Handler<LogMetrics> for MetricsCollector {
-    type Result = ResponseActFuture<Self, ()>;
-
-    fn handle(&mut self, _msg: LogMetrics, _ctx: &mut Context<Self>) -> Self::Result {
-        self.log_metrics();
-
-        // Loop with printer_period time
-        Box::pin(
-            sleep(Duration::from_millis(self.printer_period))
-                .into_actor(self)
-                .map(move |_result, _me, ctx| {
-                    ctx.address().try_send(LogMetrics {}).expect(
-                        "[CRITICAL] Could not auto-send LogMetrics msg to MetricsCollector",
-                    );
-                }),
-        )
-    }
+/// Message to get the `n` most booked routes.
+#[derive(Message)]
+#[rtype(result = "MostBookedRoutes")]
+pub struct GetMostBookedRoutes {
+    pub n: usize,
 }
 
+// HANDLERS -------------------------------------------------------------------
+
 impl Handler<MetricsMessage> for MetricsCollector {
     type Result = ();
 
@@ -235,23 +261,45 @@ impl Handler<MetricsMessage> for MetricsCollector {
         MetricsMessage {
             start_time,
             end_time,
-            origin,
-            destiny,
+            routes,
+            passengers,
         }: MetricsMessage,
         _ctx: &mut Context<Self>,
     ) {
-        let time = end_time - start_time;
+        self.metrics
+            .add_request(routes, passengers, end_time - start_time);
+    }
+}
+
+impl Handler<RequestRejected> for MetricsCollector {
+    type Result = ();
+
+    fn handle(&mut self, _msg: RequestRejected, _ctx: &mut Context<Self>) {
+        self.metrics.add_rejected();
+    }
+}
 
-        let route_key = Route { origin, destiny };
+impl Handler<InventoryUpdate> for MetricsCollector {
+    type Result = ();
 
-        self.metrics.n_reqs += 1;
-        self.metrics.reqs_duration_cumsum += time;
+    fn handle(
+        &mut self,
+        InventoryUpdate { airline, flights }: InventoryUpdate,
+        _ctx: &mut Context<Self>,
+    ) {
+        self.inventories.insert(airline, flights);
+    }
+}
 
-        if let Some(route_count) = self.metrics.routes_booking_count.get_mut(&route_key) {
-            *route_count += 1;
-        } else {
-            self.metrics.routes_booking_count.insert(route_key, 1);
-        }
+impl Handler<WebServiceUpdate> for MetricsCollector {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        WebServiceUpdate { name, metrics }: WebServiceUpdate,
+        _ctx: &mut Context<Self>,
+    ) {
+        self.webservices.insert(name, metrics);
     }
 }
 
@@ -267,18 +315,34 @@ impl Handler<GetMetrics> for MetricsCollector {
             &self.logger_addr,
             "[MetricsCollector] Metrics request received".to_string(),
         );
-        let mut req_mean_time = 0;
+        Ok(MetricsResponse {
+            req_mean_time: self.metrics.mean_time(),
+            most_booked_routes: self.get_n_most_booked_routes(),
+            n_req: self.metrics.n_reqs(),
+            n_passengers: self.metrics.n_passengers(),
+            n_rejected: self.metrics.n_rejected(),
+            seats_remaining: self.inventories.clone(),
+            webservices: self.webservices.clone(),
+        })
+    }
+}
 
-        if self.metrics.n_reqs > 0 {
-            req_mean_time = self.metrics.reqs_duration_cumsum / (self.metrics.n_reqs as i64);
-        }
+impl Handler<GetMostBookedRoutes> for MetricsCollector {
+    type Result = MessageResult<GetMostBookedRoutes>;
+
+    fn handle(
+        &mut self,
+        GetMostBookedRoutes { n }: GetMostBookedRoutes,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        MessageResult(self.metrics.most_booked_routes(n))
+    }
+}
 
-        let most_booked_routes = self.get_n_most_booked_routes();
+impl Handler<Ping> for MetricsCollector {
+    type Result = Pong;
 
-        Ok(MetricsResponse {
-            req_mean_time,
-            most_booked_routes,
-            n_req: self.metrics.n_reqs,
-        })
+    fn handle(&mut self, _msg: Ping, _ctx: &mut Context<Self>) -> Self::Result {
+        Pong::default()
     }
 }
@@ -1,15 +1,21 @@
 // Public
 pub mod errors;
+pub mod health;
 pub mod logger;
 pub mod metrics;
+pub mod offers;
 pub mod request;
 pub mod request_handler;
 pub mod routes;
 pub mod state;
 pub mod status_service;
+pub mod store;
+pub mod tls;
+pub mod webhooks;
 
 // Private
 mod airlines;
+mod archive;
 mod dispatcher;
 mod hotel;
 mod webservice;
@@ -0,0 +1,295 @@
+//! Offers service.
+//!
+//! Promotional offers are generated periodically for the most booked routes, with
+//! the discount of the tier each route ranks in. Requests may use an offer (`offer_id`),
+//! and every completed booking that used it is counted, along with the times it was
+//! served, to measure its conversion.
+
+use std::{collections::HashMap, time::Duration};
+
+use actix::{
+    Actor, ActorFutureExt, Addr, AsyncContext, Context, Handler, Message, MessageResult,
+    ResponseActFuture, Supervised, WrapFuture,
+};
+use serde::Serialize;
+
+use crate::common::{
+    config::OffersConfig,
+    metrics::{MostBookedRoutes, RouteMetrics},
+    request::Route,
+    utils::{now, uuid},
+};
+use crate::part2::{
+    errors::OfferError,
+    logger::Logger,
+    metrics::{GetMostBookedRoutes, MetricsCollector},
+};
+
+// TYPES ----------------------------------------------------------------------
+
+/// Promotional offer for a route, valid between `valid_from` and `valid_until` (ms).
+/// `views` counts the times it was served and `bookings` the completed requests that used it.
+#[derive(Clone, Serialize)]
+pub struct Offer {
+    pub id: String,
+    pub route: Route,
+    pub rank: usize,
+    pub discount: u8,
+    pub valid_from: i64,
+    pub valid_until: i64,
+    pub views: u64,
+    pub bookings: u64,
+}
+
+// ACTOR ----------------------------------------------------------------------
+
+/// OffersService is an entity <Actor>. It will be in charge of refreshing the offers
+/// every `refresh_period` ms from the MetricsCollector most booked routes, serving
+/// them and counting their conversions. Only the latest offer of each route is served,
+/// but older ones can still be used until they expire.
+
+pub struct OffersService {
+    offers: HashMap<String, Offer>,
+    current: HashMap<Route, String>,
+    config: OffersConfig,
+    logger: Addr<Logger>,
+    metrics_collector: Addr<MetricsCollector>,
+}
+
+impl OffersService {
+    /// Given an OffersConfig, an Addr Logger and an Addr MetricsCollector this method
+    /// will create an OffersService entity, without offers until the first refresh.
+
+    pub fn new(
+        config: OffersConfig,
+        logger: Addr<Logger>,
+        metrics_collector: Addr<MetricsCollector>,
+    ) -> Self {
+        OffersService {
+            offers: HashMap::new(),
+            current: HashMap::new(),
+            config,
+            logger,
+            metrics_collector,
+        }
+    }
+
+    /// Given an OffersService addr this method is used to report a completed booking that used an offer.
+
+    pub fn collect_booking(offers: &Addr<OffersService>, offer_id: String) {
+        if offers.try_send(OfferBooked { offer_id }).is_err() {
+            println!("Warning: failed to send booking to OffersService");
+        };
+    }
+
+    fn refresh(&mut self, most_booked_routes: MostBookedRoutes) {
+        let now = now();
+        self.drop_expired(now);
+
+        let mut current = HashMap::new();
+        for (i, RouteMetrics { route, .. }) in most_booked_routes.into_iter().enumerate() {
+            let rank = i + 1;
+            let discount = match self.config.discount_for(rank) {
+                Some(discount) => discount,
+                None => break,
+            };
+
+            // Keep the route offer while its discount did not change, unless it would
+            // expire before the next refresh (it can still be used until it expires)
+            let offers = &mut self.offers;
+            let next_refresh = now + self.config.refresh_period as i64;
+            let kept = self
+                .current
+                .get(&route)
+                .and_then(|offer_id| offers.get_mut(offer_id))
+                .filter(|offer| offer.discount == discount && offer.valid_until > next_refresh);
+            if let Some(offer) = kept {
+                offer.rank = rank;
+                current.insert(route, offer.id.clone());
+                continue;
+            }
+
+            let offer = Offer {
+                id: uuid(),
+                route: route.clone(),
+                rank,
+                discount,
+                valid_from: now,
+                valid_until: now + self.config.validity as i64,
+                views: 0,
+                bookings: 0,
+            };
+            Logger::send_to(
+                &self.logger,
+                format!(
+                    "[OffersService] New offer {}: {}% off {} -> {} (rank {})",
+                    offer.id, discount, route.origin, route.destiny, rank
+                ),
+            );
+            current.insert(route, offer.id.clone());
+            self.offers.insert(offer.id.clone(), offer);
+        }
+        self.current = current;
+    }
+
+    fn drop_expired(&mut self, now: i64) {
+        let expired: Vec<String> = self
+            .offers
+            .values()
+            .filter(|offer| offer.valid_until <= now)
+            .map(|offer| offer.id.clone())
+            .collect();
+
+        for offer_id in expired {
+            if let Some(offer) = self.offers.remove(&offer_id) {
+                Logger::send_to(
+                    &self.logger,
+                    format!(
+                        "[OffersService] Offer {} expired (views: {}, bookings: {})",
+                        offer.id, offer.views, offer.bookings
+                    ),
+                );
+            }
+        }
+        let offers = &self.offers;
+        self.current
+            .retain(|_route, offer_id| offers.contains_key(offer_id));
+    }
+}
+
+impl Actor for OffersService {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        Logger::send_to(&self.logger, "[OffersService] Started".to_string());
+        ctx.notify(RefreshOffers {});
+    }
+}
+
+impl Supervised for OffersService {
+    fn restarting(&mut self, _ctx: &mut Context<Self>) {
+        Logger::send_to(&self.logger, "[OffersService] Restarting".to_string());
+    }
+}
+
+// MESSAGES -------------------------------------------------------------------
+
+/// Message to refresh the offers from the most booked routes.
+#[derive(Message)]
+#[rtype(result = "()")]
+struct RefreshOffers;
+
+/// Message to get the current offers.
+#[derive(Message)]
+#[rtype(result = "Vec<Offer>")]
+pub struct GetOffers;
+
+/// Message to check an offer can be used to book the given route.
+#[derive(Message)]
+#[rtype(result = "Result<(), OfferError>")]
+pub struct CheckOffer {
+    pub offer_id: String,
+    pub route: Route,
+}
+
+/// Message that indicates a request that used an offer was completed.
+#[derive(Message)]
+#[rtype(result = "()")]
+struct OfferBooked {
+    offer_id: String,
+}
+
+// HANDLERS -------------------------------------------------------------------
+
+impl Handler<RefreshOffers> for OffersService {
+    type Result = ResponseActFuture<Self, ()>;
+
+    fn handle(&mut self, _msg: RefreshOffers, _ctx: &mut Context<Self>) -> Self::Result {
+        let n = self.config.n_routes();
+
+        // Loop with refresh_period time
+        Box::pin(
+            self.metrics_collector
+                .send(GetMostBookedRoutes { n })
+                .into_actor(self)
+                .map(|result, me, ctx| {
+                    match result {
+                        Ok(most_booked_routes) => me.refresh(most_booked_routes),
+                        Err(err) => Logger::send_to(
+                            &me.logger,
+                            format!(
+                                "[OffersService] [WARNING] Could not get most booked routes: {}",
+                                err
+                            ),
+                        ),
+                    }
+                    ctx.notify_later(
+                        RefreshOffers {},
+                        Duration::from_millis(me.config.refresh_period),
+                    );
+                }),
+        )
+    }
+}
+
+impl Handler<GetOffers> for OffersService {
+    type Result = MessageResult<GetOffers>;
+
+    fn handle(&mut self, _msg: GetOffers, _ctx: &mut Context<Self>) -> Self::Result {
+        let now = now();
+        let mut offers = Vec::new();
+        for offer_id in self.current.values() {
+            let offer = self
+                .offers
+                .get_mut(offer_id)
+                .filter(|offer| offer.valid_until > now);
+            if let Some(offer) = offer {
+                offer.views += 1;
+                offers.push(offer.clone());
+            }
+        }
+        offers.sort_by_key(|offer| offer.rank);
+
+        MessageResult(offers)
+    }
+}
+
+impl Handler<CheckOffer> for OffersService {
+    type Result = Result<(), OfferError>;
+
+    fn handle(
+        &mut self,
+        CheckOffer { offer_id, route }: CheckOffer,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        let offer = self
+            .offers
+            .get(&offer_id)
+            .ok_or(OfferError::OfferNotFound)?;
+
+        if offer.valid_until <= now() {
+            return Err(OfferError::OfferExpired);
+        }
+        if offer.route != route {
+            return Err(OfferError::RouteMismatch(offer.route.clone()));
+        }
+        Ok(())
+    }
+}
+
+impl Handler<OfferBooked> for OffersService {
+    type Result = ();
+
+    fn handle(&mut self, OfferBooked { offer_id }: OfferBooked, _ctx: &mut Context<Self>) {
+        match self.offers.get_mut(&offer_id) {
+            Some(offer) => offer.bookings += 1,
+            None => Logger::send_to(
+                &self.logger,
+                format!(
+                    "[OffersService] [WARNING] Booking of offer {} completed after it expired",
+                    offer_id
+                ),
+            ),
+        }
+    }
+}
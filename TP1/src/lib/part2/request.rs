@@ -1,20 +1,52 @@
 //! Request entities.
 
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
-/// Incomming parsed request.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct RawRequest {
-    pub origin: String,
-    pub destiny: String,
-    pub airline: String,
-    pub package: bool,
-}
+pub use crate::common::request::{Passenger, RawRequest, Route, Stay, Stop};
 
-/// Entity that is used to keep track of petition status.
+/// Entity that is used to keep track of petition status. `correlation_id` is the
+/// `X-Request-ID` header received with the petition, logged along with its id.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Request {
     pub id: String,
     pub start_time: i64,
     pub raw_request: RawRequest,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.correlation_id {
+            Some(correlation_id) => write!(f, "{} (X-Request-ID {})", self.id, correlation_id),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// Booking of a request in a webservice: one of its itinerary legs (`leg` index) in
+/// an airline, or the whole stay in the hotel (no leg).
+#[derive(Clone, Debug)]
+pub struct Booking {
+    pub req: Request,
+    pub leg: Option<usize>,
+}
+
+impl Booking {
+    /// Whether both are the same booking: the same leg (or stay) of the same request.
+
+    pub fn is_same(&self, other: &Booking) -> bool {
+        self.req.id == other.req.id && self.leg == other.leg
+    }
+
+    /// Returns the route booked: the leg one, or the whole itinerary for the hotel.
+
+    pub fn route(&self) -> Route {
+        let raw_request = &self.req.raw_request;
+        self.leg
+            .and_then(|leg| raw_request.legs().into_iter().nth(leg))
+            .map_or_else(|| raw_request.route(), |leg| leg.route)
+    }
 }
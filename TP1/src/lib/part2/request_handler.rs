@@ -2,15 +2,22 @@
 
 use actix::{Actor, Addr, Context, Handler, Message};
 
-use crate::common::{paths, utils, utils::now};
+use crate::common::{
+    config::{AirlinesConfig, HotelConfig},
+    http_client::Url,
+    utils,
+    utils::now,
+};
 use crate::part2::{
     airlines::{self, Airline, Airlines},
-    dispatcher::HandleBook,
+    dispatcher::{HandleBook, WebServiceDispatcher},
     errors::*,
+    health::{Ping, Pong},
     hotel::{self, Hotel},
     logger::Logger,
-    request::{RawRequest, Request},
-    status_service::{NewRequest, StatusService},
+    metrics::MetricsCollector,
+    request::{Booking, RawRequest, Request},
+    status_service::{NewRequest, RegisterDispatchers, StatusService},
 };
 
 // ACTOR ----------------------------------------------------------------------
@@ -20,6 +27,8 @@ use crate::part2::{
 pub struct RequestHandler {
     airlines: Airlines,
     hotel: Hotel,
+    hotel_name: String,
+    webhooks_enabled: bool,
     logger: Addr<Logger>,
     status_service: Addr<StatusService>,
 }
@@ -29,26 +38,56 @@ impl Actor for RequestHandler {
 
     fn started(&mut self, _: &mut Self::Context) {
         Logger::send_to(&self.logger, "[RequestHandler] Started".to_string());
+        let dispatchers = RegisterDispatchers {
+            airlines: self.airlines.clone(),
+            hotel: self.hotel.clone(),
+        };
+        if self.status_service.try_send(dispatchers).is_err() {
+            Logger::send_to(
+                &self.logger,
+                "[RequestHandler] [CRITICAL] Could not register dispatchers in StatusService"
+                    .to_string(),
+            );
+        }
     }
 }
 
 impl RequestHandler {
-    /// Given an Addr Logger, Addr StatusService it will return a RequestHandler with its corresponding
-    /// Airlines Dispatchers, Hotel Dispatcher and associated services.
-
-    pub fn new(logger: Addr<Logger>, status_service: Addr<StatusService>) -> Self {
-        let airlines = airlines::from_path(
-            paths::AIRLINES_CONFIG,
+    /// Given an AirlinesConfig, a HotelConfig, an optional seed, whether webhooks are enabled, an Addr Logger,
+    /// an Addr MetricsCollector and an Addr StatusService
+    /// it will return a RequestHandler with its corresponding Airlines Dispatchers, Hotel Dispatcher
+    /// and associated services.
+
+    pub fn new(
+        airlines_config: AirlinesConfig,
+        hotel_config: HotelConfig,
+        seed: Option<u64>,
+        webhooks_enabled: bool,
+        logger: Addr<Logger>,
+        metrics_collector: Addr<MetricsCollector>,
+        status_service: Addr<StatusService>,
+    ) -> Self {
+        let airlines = airlines::from_config(
+            airlines_config,
+            seed,
+            logger.clone(),
+            metrics_collector.clone(),
+            status_service.clone(),
+        );
+        let hotel_name = hotel_config.name.clone();
+        let hotel = hotel::from_config(
+            hotel_config,
+            seed,
             logger.clone(),
+            metrics_collector,
             status_service.clone(),
-        )
-        .expect("[CRITICAL] Error while initializing airlines web services");
-        let hotel = hotel::from_path(paths::HOTEL_CONFIG, logger.clone(), status_service.clone())
-            .expect("[CRITICAL] Error while initializing hotel web service");
+        );
 
         RequestHandler {
             airlines,
             hotel,
+            hotel_name,
+            webhooks_enabled,
             logger,
             status_service,
         }
@@ -60,23 +99,65 @@ impl RequestHandler {
 #[derive(Message)]
 #[rtype(result = "Result<String, HandlerError>")]
 
-/// Message to dispatch a new petition to its corresponding WebService dispatcher.
+/// Message to dispatch a new petition to its corresponding WebService dispatcher,
+/// tagged with its optional `X-Request-ID` correlation id.
 pub struct HandleRequest {
     pub raw_request: RawRequest,
+    pub correlation_id: Option<String>,
 }
 
+/// Message to get the dispatcher of every airline and the hotel, by webservice name.
+#[derive(Message)]
+#[rtype(result = "Vec<(String, Addr<WebServiceDispatcher>)>")]
+pub struct GetDispatchers;
+
 // HANDLERS -------------------------------------------------------------------
 
 impl Handler<HandleRequest> for RequestHandler {
     type Result = Result<String, HandlerError>;
 
     fn handle(&mut self, msg: HandleRequest, _: &mut Context<Self>) -> Self::Result {
-        let raw_request = msg.raw_request;
+        let HandleRequest {
+            raw_request,
+            correlation_id,
+        } = msg;
+        raw_request
+            .validate_itinerary()
+            .map_err(HandlerError::InvalidItinerary)?;
+        raw_request
+            .validate_passengers()
+            .map_err(HandlerError::InvalidPassengers)?;
+        if let Some(callback_url) = &raw_request.callback_url {
+            if !self.webhooks_enabled {
+                return Err(HandlerError::InvalidCallbackUrl(String::from(
+                    "webhooks are disabled (no secret configured)",
+                )));
+            }
+            let url = Url::parse(callback_url)
+                .map_err(|err| HandlerError::InvalidCallbackUrl(err.to_string()))?;
+            // Names are resolved and checked again when delivering
+            if url.has_internal_host() {
+                return Err(HandlerError::InvalidCallbackUrl(String::from(
+                    "loopback, private and link-local hosts are not allowed",
+                )));
+            }
+        }
+        let airlines = raw_request
+            .legs()
+            .into_iter()
+            .map(|leg| {
+                self.airlines
+                    .get(&leg.airline)
+                    .ok_or(HandlerError::AirlineNotFound(leg.airline))
+            })
+            .collect::<Result<Vec<&Airline>, _>>()?;
+
         let req_id = utils::uuid();
         let req = Request {
             id: req_id.clone(),
             start_time: now(),
             raw_request: raw_request.clone(),
+            correlation_id,
         };
 
         // In a real system, we should run the following
@@ -89,23 +170,53 @@ impl Handler<HandleRequest> for RequestHandler {
             .try_send(NewRequest { req: req.clone() })
             .map_err(|_| HandlerError::StatusServiceUnavailable)?;
 
-        let airline: &Airline = self
-            .airlines
-            .get(&raw_request.airline)
-            .ok_or(HandlerError::AirlineNotFound)?;
-
         if raw_request.package {
             self.hotel
-                .try_send(HandleBook { req: req.clone() })
+                .try_send(HandleBook {
+                    booking: Booking {
+                        req: req.clone(),
+                        leg: None,
+                    },
+                })
                 .map_err(|_| HandlerError::HotelUnavailable)?;
         }
 
-        airline
-            .try_send(HandleBook { req: req.clone() })
-            .map_err(|_| HandlerError::AirlineUnavailable)?;
+        for (leg, airline) in airlines.into_iter().enumerate() {
+            airline
+                .try_send(HandleBook {
+                    booking: Booking {
+                        req: req.clone(),
+                        leg: Some(leg),
+                    },
+                })
+                .map_err(|_| HandlerError::AirlineUnavailable)?;
+        }
 
         Logger::send_to(&self.logger, format!("[RequestHandler] {:#?}", req));
 
         Ok(req_id)
     }
 }
+
+impl Handler<GetDispatchers> for RequestHandler {
+    type Result = Vec<(String, Addr<WebServiceDispatcher>)>;
+
+    fn handle(&mut self, _msg: GetDispatchers, _ctx: &mut Context<Self>) -> Self::Result {
+        let mut dispatchers = self
+            .airlines
+            .iter()
+            .map(|(name, airline)| (name.clone(), airline.clone()))
+            .collect::<Vec<_>>();
+        dispatchers.sort_by(|(a, _), (b, _)| a.cmp(b));
+        dispatchers.push((self.hotel_name.clone(), self.hotel.clone()));
+        dispatchers
+    }
+}
+
+impl Handler<Ping> for RequestHandler {
+    type Result = Pong;
+
+    fn handle(&mut self, _msg: Ping, _ctx: &mut Context<Self>) -> Self::Result {
+        Pong::default()
+    }
+}
@@ -1,17 +1,30 @@
 //! Module with actix request handlers.
 
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{
+    guard,
+    http::{header, Method},
+    web, HttpRequest, HttpResponse, Resource, Responder,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::part2::{
+    dispatcher::SetPaused,
     errors::*,
+    health::{self, HealthResponse, HealthStatus},
     metrics::GetMetrics,
-    request::{RawRequest, Request},
-    request_handler::HandleRequest,
+    offers::{CheckOffer, GetOffers},
+    request::{RawRequest, Request, Route},
+    request_handler::{GetDispatchers, HandleRequest},
     state::ServerState,
-    status_service::{GetStatus, RequestStatus},
+    status_service::{GetStatus, RequestEvent, RequestStatus, StatusResponse},
 };
 
+/// OpenAPI 3 document describing every route of the API.
+pub const OPENAPI_SPEC: &str = include_str!("../../../docs/openapi.json");
+
+/// Correlation header, propagated into the logs of the request and echoed in the response.
+pub const REQUEST_ID_HEADER: &str = "X-Request-ID";
+
 // TYPES ---------------------------------------------------------------
 
 #[derive(Deserialize)]
@@ -20,25 +33,124 @@ pub struct GetStatusQuery {
 }
 
 #[derive(Serialize)]
-struct StatusResponse {
+struct EventsResponse {
     id: String,
-    airline: String,
-    origin: String,
-    destiny: String,
-    package: bool,
-    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<String>,
+    events: Vec<RequestEvent>,
+}
+
+// CONFIGURATION --------------------------------------------------------------
+
+/// Route of the API: the resource at `path` answered by its handler for `method` requests.
+struct ApiRoute {
+    method: Method,
+    path: &'static str,
+    handler: fn(Resource) -> Resource,
+}
+
+/// Every route of the API. Routes must be documented in the OpenAPI document.
+const ROUTES: [ApiRoute; 11] = [
+    ApiRoute {
+        method: Method::GET,
+        path: "/",
+        handler: |resource| resource.to(get_index),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/health/live",
+        handler: |resource| resource.to(get_health_live),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/health/ready",
+        handler: |resource| resource.to(get_health_ready),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/openapi.json",
+        handler: |resource| resource.to(get_openapi),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/metrics",
+        handler: |resource| resource.to(get_metrics),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/offers",
+        handler: |resource| resource.to(get_offers),
+    },
+    ApiRoute {
+        method: Method::POST,
+        path: "/request",
+        handler: |resource| resource.to(post_request),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/request",
+        handler: |resource| resource.to(get_request),
+    },
+    ApiRoute {
+        method: Method::GET,
+        path: "/request/{id}/events",
+        handler: |resource| resource.to(get_request_events),
+    },
+    ApiRoute {
+        method: Method::POST,
+        path: "/admin/dispatchers/{webservice}/pause",
+        handler: |resource| resource.to(post_dispatcher_pause),
+    },
+    ApiRoute {
+        method: Method::POST,
+        path: "/admin/dispatchers/{webservice}/resume",
+        handler: |resource| resource.to(post_dispatcher_resume),
+    },
+];
+
+/// Registers every route of the API.
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    for route in &ROUTES {
+        // Guarded by method, so requests with other methods are matched against the next routes
+        let resource = web::resource(route.path).guard(guard::Method(route.method.clone()));
+        cfg.service((route.handler)(resource));
+    }
 }
 
 // GET INDEX ------------------------------------------------------------------
 
-#[get("/")]
 pub async fn get_index() -> impl Responder {
     HttpResponse::Ok()
 }
 
+// GET HEALTH -----------------------------------------------------------------
+
+pub async fn get_health_live(state: web::Data<ServerState>) -> impl Responder {
+    health_response(health::check_live(&state).await)
+}
+
+pub async fn get_health_ready(state: web::Data<ServerState>) -> impl Responder {
+    health_response(health::check_ready(&state).await)
+}
+
+fn health_response(health: HealthResponse) -> HttpResponse {
+    match health.status {
+        HealthStatus::Up => HttpResponse::Ok().json(health),
+        HealthStatus::Down => HttpResponse::ServiceUnavailable().json(health),
+    }
+}
+
+// GET OPENAPI ----------------------------------------------------------------
+
+pub async fn get_openapi() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(OPENAPI_SPEC)
+}
+
 // GET METRICS ----------------------------------------------------------------
 
-#[get("/metrics")]
 pub async fn get_metrics(state: web::Data<ServerState>) -> impl Responder {
     let msg = GetMetrics {};
     match state.metrics_collector.send(msg).await {
@@ -52,22 +164,83 @@ pub async fn get_metrics(state: web::Data<ServerState>) -> impl Responder {
     }
 }
 
+// GET OFFERS -----------------------------------------------------------------
+
+pub async fn get_offers(state: web::Data<ServerState>) -> impl Responder {
+    match state.offers.send(GetOffers {}).await {
+        Ok(offers) => HttpResponse::Ok().json(offers),
+        Err(err) => {
+            HttpResponse::InternalServerError().body(format!("Internal Server Error: {}", err))
+        }
+    }
+}
+
 // POST REQUEST ---------------------------------------------------------------
 
-#[post("/request")]
 pub async fn post_request(
+    req: HttpRequest,
     raw_request: web::Json<RawRequest>,
     state: web::Data<ServerState>,
 ) -> impl Responder {
+    let correlation_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(String::from);
+
+    if let Some(offer_id) = &raw_request.offer_id {
+        let msg = CheckOffer {
+            offer_id: offer_id.clone(),
+            route: raw_request.route(),
+        };
+        match state.offers.send(msg).await {
+            Ok(Ok(())) => (),
+            Ok(Err(OfferError::OfferNotFound)) => {
+                return HttpResponse::NotFound().body(format!("Offer {} not found", offer_id))
+            }
+            Ok(Err(OfferError::OfferExpired)) => {
+                return HttpResponse::BadRequest().body(format!("Offer {} expired", offer_id))
+            }
+            Ok(Err(OfferError::RouteMismatch(Route { origin, destiny }))) => {
+                return HttpResponse::BadRequest().body(format!(
+                    "Offer {} is only valid for route {} -> {}",
+                    offer_id, origin, destiny
+                ))
+            }
+            Err(err) => {
+                return HttpResponse::InternalServerError()
+                    .body(format!("Internal Server Error: {}", err))
+            }
+        }
+    }
+
     let request_handler = &state.request_handler;
     let msg = HandleRequest {
         raw_request: raw_request.clone(),
+        correlation_id: correlation_id.clone(),
     };
 
     match request_handler.send(msg).await {
-        Ok(Ok(req_id)) => HttpResponse::Created().body(req_id),
-        Ok(Err(HandlerError::AirlineNotFound)) => {
-            HttpResponse::NotFound().body(format!("Airline {} not found", raw_request.airline))
+        Ok(Ok(req_id)) => {
+            let mut response = HttpResponse::Created();
+            if let Some(correlation_id) = correlation_id {
+                response.insert_header((REQUEST_ID_HEADER, correlation_id));
+            }
+            response.body(req_id)
+        }
+        Ok(Err(HandlerError::InvalidItinerary(reason))) => {
+            HttpResponse::BadRequest().body(format!("Invalid itinerary: {}", reason))
+        }
+        Ok(Err(HandlerError::InvalidPassengers(reason))) => {
+            HttpResponse::BadRequest().body(format!("Invalid passengers: {}", reason))
+        }
+        Ok(Err(HandlerError::InvalidCallbackUrl(reason))) => {
+            HttpResponse::BadRequest().body(format!("Invalid callback_url: {}", reason))
+        }
+        Ok(Err(HandlerError::AirlineNotFound(airline))) => {
+            HttpResponse::NotFound().body(format!("Airline {} not found", airline))
         }
         Ok(Err(HandlerError::AirlineUnavailable)) => HttpResponse::NotFound().body(format!(
             "Airline {} not available, try later",
@@ -86,7 +259,6 @@ pub async fn post_request(
 
 // GET REQUEST ----------------------------------------------------------------
 
-#[get("/request")]
 pub async fn get_request(
     query: web::Query<GetStatusQuery>,
     state: web::Data<ServerState>,
@@ -98,38 +270,40 @@ pub async fn get_request(
         })
         .await
     {
-        Ok(Ok(RequestStatus {
-            req:
-                Request {
-                    id,
-                    start_time: _,
-                    raw_request:
-                        RawRequest {
-                            origin,
-                            destiny,
-                            airline,
-                            package,
-                        },
-                },
-            pending_airline,
-            pending_hotel,
-        })) => {
-            let status = if pending_airline || pending_hotel {
-                String::from("PENDING")
-            } else {
-                String::from("COMPLETED")
-            };
-
-            let response = StatusResponse {
-                id,
-                airline,
-                origin,
-                destiny,
-                package,
-                status,
-            };
-            HttpResponse::Ok().json(response)
+        Ok(Ok(req_status)) => HttpResponse::Ok().json(StatusResponse::from(req_status)),
+        Ok(Err(StatusServiceError::RequestNotFound)) => {
+            HttpResponse::NotFound().body("Request not found")
         }
+        Err(err) => {
+            HttpResponse::InternalServerError().body(format!("Internal Server Error: {}", err))
+        }
+    }
+}
+
+// GET REQUEST EVENTS ---------------------------------------------------------
+
+pub async fn get_request_events(
+    id: web::Path<String>,
+    state: web::Data<ServerState>,
+) -> impl Responder {
+    match state
+        .status_service
+        .send(GetStatus {
+            req_id: id.into_inner(),
+        })
+        .await
+    {
+        Ok(Ok(RequestStatus {
+            req: Request {
+                id, correlation_id, ..
+            },
+            events,
+            ..
+        })) => HttpResponse::Ok().json(EventsResponse {
+            id,
+            correlation_id,
+            events,
+        }),
         Ok(Err(StatusServiceError::RequestNotFound)) => {
             HttpResponse::NotFound().body("Request not found")
         }
@@ -138,3 +312,446 @@ pub async fn get_request(
         }
     }
 }
+
+// POST DISPATCHER PAUSE / RESUME ---------------------------------------------
+
+pub async fn post_dispatcher_pause(
+    req: HttpRequest,
+    webservice: web::Path<String>,
+    state: web::Data<ServerState>,
+) -> impl Responder {
+    if let Err(response) = authorize_admin(&req, &state.admin_token) {
+        return response;
+    }
+    set_paused(&state, &webservice, true).await
+}
+
+pub async fn post_dispatcher_resume(
+    req: HttpRequest,
+    webservice: web::Path<String>,
+    state: web::Data<ServerState>,
+) -> impl Responder {
+    if let Err(response) = authorize_admin(&req, &state.admin_token) {
+        return response;
+    }
+    set_paused(&state, &webservice, false).await
+}
+
+/// Given an admin request and the configured admin token this function checks the request
+/// carries it as a bearer token, returning the error response otherwise.
+
+fn authorize_admin(req: &HttpRequest, admin_token: &str) -> Result<(), HttpResponse> {
+    if admin_token.is_empty() {
+        return Err(HttpResponse::Forbidden().body("Admin routes are disabled"));
+    }
+
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if tokens_match(token, admin_token) => Ok(()),
+        _ => Err(HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .body("Missing or invalid admin token")),
+    }
+}
+
+/// Compares every byte of both tokens, so the time taken does not reveal how much of it matched.
+
+fn tokens_match(token: &str, expected: &str) -> bool {
+    token.len() == expected.len()
+        && token
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn set_paused(state: &ServerState, webservice: &str, paused: bool) -> HttpResponse {
+    let dispatcher = match state.request_handler.send(GetDispatchers).await {
+        Ok(dispatchers) => dispatchers
+            .into_iter()
+            .find(|(name, _)| name == webservice)
+            .map(|(_, dispatcher)| dispatcher),
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Internal Server Error: {}", err))
+        }
+    };
+    let dispatcher = match dispatcher {
+        Some(dispatcher) => dispatcher,
+        None => {
+            return HttpResponse::NotFound().body(format!("Webservice {} not found", webservice))
+        }
+    };
+
+    match dispatcher.send(SetPaused { paused }).await {
+        Ok(dispatcher_state) => HttpResponse::Ok().json(dispatcher_state),
+        Err(err) => {
+            HttpResponse::InternalServerError().body(format!("Internal Server Error: {}", err))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use actix_web::{
+        http::{Method, StatusCode},
+        test::{call_service, init_service, TestRequest},
+        App,
+    };
+    use chrono::NaiveDate;
+    use serde::Serialize;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::common::{
+        inventory::FlightInventory,
+        metrics::{RouteMetrics, WebServiceMetrics, WebServicesMetrics},
+        request::PassengerType,
+    };
+    use crate::part2::{
+        dispatcher::DispatcherState,
+        health::ComponentHealth,
+        metrics::MetricsResponse,
+        offers::Offer,
+        request::{Passenger, Stay, Stop},
+        status_service::{DeliveryAttempt, EventKind},
+    };
+
+    const HTTP_METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+    fn spec() -> Value {
+        serde_json::from_str(OPENAPI_SPEC).expect("OpenAPI document is not valid JSON")
+    }
+
+    /// (method, path) of every operation in the OpenAPI document.
+    fn documented_operations() -> BTreeSet<(String, String)> {
+        let spec = spec();
+        let paths = spec["paths"]
+            .as_object()
+            .expect("OpenAPI document without paths");
+
+        paths
+            .iter()
+            .flat_map(|(path, item)| {
+                item.as_object()
+                    .expect("OpenAPI path without operations")
+                    .keys()
+                    .filter(|key| HTTP_METHODS.contains(&key.as_str()))
+                    .map(move |method| (method.clone(), path.clone()))
+            })
+            .collect()
+    }
+
+    /// (method, path) of every route registered by `configure`.
+    fn declared_routes() -> BTreeSet<(String, String)> {
+        ROUTES
+            .iter()
+            .map(|route| (route.method.as_str().to_lowercase(), route.path.to_string()))
+            .collect()
+    }
+
+    fn schema_properties(name: &str) -> BTreeSet<String> {
+        spec()["components"]["schemas"][name]["properties"]
+            .as_object()
+            .unwrap_or_else(|| panic!("Schema {} is not documented", name))
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    fn schema_required(name: &str) -> BTreeSet<String> {
+        spec()["components"]["schemas"][name]["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|field| field.as_str().map(String::from))
+            .collect()
+    }
+
+    fn serialized_fields<T: Serialize>(value: &T) -> BTreeSet<String> {
+        serde_json::to_value(value)
+            .expect("Could not serialize sample")
+            .as_object()
+            .expect("Sample is not serialized as an object")
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// Checks the schema documents every field of the complete sample, and that its
+    /// required fields are serialized even in the minimal sample.
+    fn assert_schema<T: Serialize>(name: &str, complete: &T, minimal: &T) {
+        assert_eq!(
+            serialized_fields(complete),
+            schema_properties(name),
+            "Schema {} does not match its type",
+            name
+        );
+        let missing: Vec<String> = schema_required(name)
+            .difference(&serialized_fields(minimal))
+            .cloned()
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "Schema {} requires fields that may be missing: {:?}",
+            name,
+            missing
+        );
+    }
+
+    fn route() -> Route {
+        Route {
+            origin: String::from("EZE"),
+            destiny: String::from("MAD"),
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2021, 10, day)
+    }
+
+    /// Replaces every `{param}` of an OpenAPI path with a sample value.
+    fn with_sample_params(path: &str) -> String {
+        path.split('/')
+            .map(
+                |segment| match segment.starts_with('{') && segment.ends_with('}') {
+                    true => "sample",
+                    false => segment,
+                },
+            )
+            .collect::<Vec<&str>>()
+            .join("/")
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        assert_eq!(declared_routes(), documented_operations());
+    }
+
+    #[actix_web::test]
+    async fn every_documented_operation_is_served() {
+        let app = init_service(App::new().configure(configure)).await;
+
+        for (method, path) in documented_operations() {
+            let method = Method::from_bytes(method.to_uppercase().as_bytes())
+                .expect("Invalid OpenAPI method");
+            let req = TestRequest::default()
+                .method(method.clone())
+                .uri(&with_sample_params(&path))
+                .to_request();
+            let res = call_service(&app, req).await;
+            assert_ne!(
+                res.status(),
+                StatusCode::NOT_FOUND,
+                "{} {} is documented but not served",
+                method,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn admin_routes_require_the_admin_token() {
+        let req = |authorization: Option<&str>| {
+            authorization
+                .into_iter()
+                .fold(TestRequest::default(), |req, value| {
+                    req.insert_header((header::AUTHORIZATION, value))
+                })
+                .to_http_request()
+        };
+        let status = |result: Result<(), HttpResponse>| result.err().map(|res| res.status());
+
+        assert_eq!(
+            status(authorize_admin(&req(Some("Bearer secret")), "")),
+            Some(StatusCode::FORBIDDEN)
+        );
+        for authorization in [
+            None,
+            Some("secret"),
+            Some("Bearer secre"),
+            Some("Bearer other"),
+        ] {
+            assert_eq!(
+                status(authorize_admin(&req(authorization), "secret")),
+                Some(StatusCode::UNAUTHORIZED),
+                "{:?} was authorized",
+                authorization
+            );
+        }
+        assert_eq!(
+            status(authorize_admin(&req(Some("Bearer secret")), "secret")),
+            None
+        );
+    }
+
+    #[test]
+    fn schemas_match_serialized_types() {
+        let stop = Stop {
+            airport: String::from("MAD"),
+            airline: String::from("Iberia"),
+        };
+        let passenger = Passenger {
+            name: String::from("Ana Diaz"),
+            document: String::from("30111222"),
+            passenger_type: PassengerType::Adult,
+        };
+        let stay = Stay {
+            check_in: date(1),
+            check_out: date(8),
+            nights: 7,
+        };
+        let raw_request = |complete: bool| RawRequest {
+            origin: String::from("EZE"),
+            destiny: String::from("CDG"),
+            airline: String::from("Aerolineas Argentinas"),
+            package: true,
+            stops: vec![stop.clone()]
+                .into_iter()
+                .filter(|_| complete)
+                .collect(),
+            round_trip: complete,
+            departure_date: Some(date(1)).filter(|_| complete),
+            return_date: Some(date(8)).filter(|_| complete),
+            return_airline: Some(String::from("Iberia")).filter(|_| complete),
+            passengers: vec![passenger.clone()]
+                .into_iter()
+                .filter(|_| complete)
+                .collect(),
+            offer_id: Some(String::from("offer")).filter(|_| complete),
+            callback_url: Some(String::from("https://partner.example.com/hook"))
+                .filter(|_| complete),
+        };
+        let delivery_attempt = |complete: bool| DeliveryAttempt {
+            attempt: 1,
+            timestamp: 0,
+            status_code: Some(500).filter(|_| complete),
+            error: Some(String::from("HTTP 500")).filter(|_| complete),
+            retry_in: Some(1000).filter(|_| complete),
+        };
+        let status_response = |complete: bool| {
+            let mut req_status = RequestStatus::new(Request {
+                id: String::from("id"),
+                start_time: 0,
+                raw_request: raw_request(complete),
+                correlation_id: None,
+            });
+            req_status.rejection = Some(String::from("sold out")).filter(|_| complete);
+            if complete {
+                req_status.deliveries.push(delivery_attempt(true));
+                req_status.events.push(RequestEvent {
+                    webservice: Some(String::from("Iberia")),
+                    leg: Some(0),
+                    ..RequestEvent::new(EventKind::Held)
+                });
+            }
+            StatusResponse::from(req_status)
+        };
+        let route_metrics = RouteMetrics {
+            route: route(),
+            amount: 1,
+        };
+        let flight_inventory = FlightInventory {
+            route: route(),
+            seats: 2,
+            remaining: 1,
+        };
+        let metrics_response = MetricsResponse {
+            n_req: 1,
+            n_passengers: 1,
+            req_mean_time: 1,
+            most_booked_routes: vec![route_metrics.clone()],
+            n_rejected: 0,
+            seats_remaining: BTreeMap::new(),
+            webservices: WebServicesMetrics::new(),
+        };
+        let webservice_metrics = WebServiceMetrics::default();
+        let request_event = |complete: bool| RequestEvent {
+            webservice: Some(String::from("Iberia")).filter(|_| complete),
+            leg: Some(0).filter(|_| complete),
+            detail: Some(String::from("retrying in 5 secs")).filter(|_| complete),
+            ..RequestEvent::new(EventKind::RetryScheduled)
+        };
+        let events_response = |complete: bool| EventsResponse {
+            id: String::from("id"),
+            correlation_id: Some(String::from("correlation")).filter(|_| complete),
+            events: vec![request_event(complete)],
+        };
+        let component_health = |complete: bool| ComponentHealth {
+            component: String::from("WebServiceDispatcher"),
+            webservice: Some(String::from("Iberia")).filter(|_| complete),
+            status: HealthStatus::Down,
+            queue_depth: Some(3).filter(|_| complete),
+            paused: Some(true).filter(|_| complete),
+            error: Some(String::from("deadline has elapsed")).filter(|_| complete),
+        };
+        let health_response = |complete: bool| HealthResponse {
+            status: HealthStatus::Down,
+            components: vec![component_health(complete)],
+        };
+        let dispatcher_state = DispatcherState {
+            webservice: String::from("Iberia"),
+            paused: true,
+            queue_depth: 3,
+            in_flight: 1,
+        };
+        let offer = Offer {
+            id: String::from("offer"),
+            route: route(),
+            rank: 1,
+            discount: 30,
+            valid_from: 0,
+            valid_until: 1,
+            views: 0,
+            bookings: 0,
+        };
+
+        assert_schema("RawRequest", &raw_request(true), &raw_request(false));
+        assert_schema("Stop", &stop, &stop);
+        assert_schema("Passenger", &passenger, &passenger);
+        assert_schema("Stay", &stay, &stay);
+        assert_schema(
+            "StatusResponse",
+            &status_response(true),
+            &status_response(false),
+        );
+        assert_schema("RequestEvent", &request_event(true), &request_event(false));
+        assert_schema(
+            "EventsResponse",
+            &events_response(true),
+            &events_response(false),
+        );
+        assert_schema(
+            "DeliveryAttempt",
+            &delivery_attempt(true),
+            &delivery_attempt(false),
+        );
+        assert_schema("Route", &route(), &route());
+        assert_schema("RouteMetrics", &route_metrics, &route_metrics);
+        assert_schema("FlightInventory", &flight_inventory, &flight_inventory);
+        assert_schema("MetricsResponse", &metrics_response, &metrics_response);
+        assert_schema(
+            "WebServiceMetrics",
+            &webservice_metrics,
+            &webservice_metrics,
+        );
+        assert_schema(
+            "ComponentHealth",
+            &component_health(true),
+            &component_health(false),
+        );
+        assert_schema(
+            "HealthResponse",
+            &health_response(true),
+            &health_response(false),
+        );
+        assert_schema("DispatcherState", &dispatcher_state, &dispatcher_state);
+        assert_schema("Offer", &offer, &offer);
+    }
+}
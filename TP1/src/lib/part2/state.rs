@@ -3,8 +3,8 @@
 use actix::Addr;
 
 use crate::part2::{
-    logger::Logger, metrics::MetricsCollector, request_handler::RequestHandler,
-    status_service::StatusService,
+    logger::Logger, metrics::MetricsCollector, offers::OffersService,
+    request_handler::RequestHandler, status_service::StatusService,
 };
 
 /// Entity that collects references for all the parts needed for the system to work.
@@ -14,6 +14,8 @@ pub struct ServerState {
     pub request_handler: Addr<RequestHandler>,
     pub status_service: Addr<StatusService>,
     pub logger: Addr<Logger>,
+    pub offers: Addr<OffersService>,
+    pub admin_token: String,
 }
 
 impl ServerState {
@@ -22,12 +24,16 @@ impl ServerState {
         status_service: Addr<StatusService>,
         logger: Addr<Logger>,
         metrics_collector: Addr<MetricsCollector>,
+        offers: Addr<OffersService>,
+        admin_token: String,
     ) -> Self {
         ServerState {
             request_handler,
             status_service,
             logger,
             metrics_collector,
+            offers,
+            admin_token,
         }
     }
 }
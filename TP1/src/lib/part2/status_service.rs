@@ -1,57 +1,482 @@
 //! Module for status service.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::time::Duration;
 
-use actix::{Actor, Addr, Context, Handler, Message};
-use serde::Serialize;
+use actix::{Actor, Addr, AsyncContext, Context, Handler, Message, Supervised};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 
-use crate::common::utils::now;
+use crate::common::{config::StatusServiceConfig, utils::now};
 use crate::part2::{
-    dispatcher::WebServiceType, errors::StatusServiceError, logger::Logger,
-    metrics::MetricsCollector, request::Request,
+    airlines::Airlines,
+    dispatcher::{ReleaseBooking, WebServiceDispatcher, WebServiceType},
+    errors::StatusServiceError,
+    health::{Ping, Pong},
+    hotel::Hotel,
+    logger::Logger,
+    metrics::MetricsCollector,
+    offers::OffersService,
+    request::{Booking, Passenger, RawRequest, Request, Stay, Stop},
+    store::{self, StatusStore},
+    webhooks::WebhookService,
 };
 
+// CONSTANTS ------------------------------------------------------------------
+
+/// Rejection reason of the requests left pending when the server was stopped.
+pub const INTERRUPTED_REASON: &str = "interrupted by a server restart";
+
 // TYPES ----------------------------------------------------------------------
 
 pub struct RequestNotFound;
 
-/// Provides a status for a given request
-#[derive(Clone, Serialize)]
+/// Kind of an event in the timeline of a request.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Registered,
+    Queued,
+    FetchStarted,
+    FetchFailed,
+    RetryScheduled,
+    Held,
+    Released,
+    Booked,
+    Rejected,
+    Completed,
+}
+
+/// Event in the timeline of a request, at `timestamp` (ms). Booking events carry the
+/// webservice and, for airlines, the itinerary leg.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RequestEvent {
+    pub timestamp: i64,
+    pub kind: EventKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webservice: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leg: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl RequestEvent {
+    pub fn new(kind: EventKind) -> Self {
+        RequestEvent {
+            timestamp: now(),
+            kind,
+            webservice: None,
+            leg: None,
+            detail: None,
+        }
+    }
+}
+
+/// Delivery attempt of the completion webhook of a request, at `timestamp` (ms). Holds the
+/// HTTP status answered by the callback and, if it failed, the error and the time until
+/// the next attempt (ms, none if there are no attempts left).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeliveryAttempt {
+    pub attempt: u32,
+    pub timestamp: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in: Option<u64>,
+}
+
+/// Provides a status for a given request. `pending_legs` are the indexes of the itinerary
+/// legs not booked yet. A rejected request (`rejection` is the reason) is completed right
+/// away, even if some of its bookings are still pending. `events` is its timeline and
+/// `deliveries` the attempts to post its final status to its callback URL.
+#[derive(Clone, Deserialize, Serialize)]
 pub struct RequestStatus {
     pub req: Request,
     pub pending_hotel: bool,
-    pub pending_airline: bool,
+    #[serde(default)]
+    pub pending_legs: Vec<usize>,
+    pub completed_at: Option<i64>,
+    #[serde(default)]
+    pub rejection: Option<String>,
+    #[serde(default)]
+    pub events: Vec<RequestEvent>,
+    #[serde(default)]
+    pub deliveries: Vec<DeliveryAttempt>,
+}
+
+/// Public status of a request, as served by the API and posted to its callback URL.
+#[derive(Serialize)]
+pub struct StatusResponse {
+    id: String,
+    airline: String,
+    origin: String,
+    destiny: String,
+    package: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stops: Vec<Stop>,
+    round_trip: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    departure_date: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    return_date: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    return_airline: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hotel_stay: Option<Stay>,
+    party_size: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    passengers: Vec<Passenger>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offer_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    callback_url: Option<String>,
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    held_by: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    deliveries: Vec<DeliveryAttempt>,
 }
 
 impl RequestStatus {
     pub fn new(req: Request) -> Self {
         let package = req.raw_request.package;
+        let n_legs = req.raw_request.legs().len();
 
         RequestStatus {
             req,
-            pending_airline: true,
+            pending_legs: (0..n_legs).collect(),
             pending_hotel: package,
+            completed_at: None,
+            rejection: None,
+            events: vec![RequestEvent::new(EventKind::Registered)],
+            deliveries: Vec::new(),
         }
     }
 }
 
+impl From<RequestStatus> for StatusResponse {
+    fn from(req_status: RequestStatus) -> Self {
+        let status = String::from(req_status.status());
+        let held_by = req_status.held_by();
+        let RequestStatus {
+            req: Request {
+                id, raw_request, ..
+            },
+            rejection,
+            deliveries,
+            ..
+        } = req_status;
+
+        let hotel_stay = raw_request.hotel_stay();
+        let party_size = raw_request.party_size();
+        let RawRequest {
+            origin,
+            destiny,
+            airline,
+            package,
+            stops,
+            round_trip,
+            departure_date,
+            return_date,
+            return_airline,
+            passengers,
+            offer_id,
+            callback_url,
+        } = raw_request;
+
+        StatusResponse {
+            id,
+            airline,
+            origin,
+            destiny,
+            package,
+            stops,
+            round_trip,
+            departure_date,
+            return_date,
+            return_airline,
+            hotel_stay,
+            party_size,
+            passengers,
+            offer_id,
+            callback_url,
+            status,
+            reason: rejection,
+            held_by,
+            deliveries,
+        }
+    }
+}
+
+impl RequestStatus {
+    /// Returns the status of the request: `REJECTED`, `PENDING` or `COMPLETED`.
+
+    pub fn status(&self) -> &'static str {
+        if self.rejection.is_some() {
+            "REJECTED"
+        } else if !self.pending_legs.is_empty() || self.pending_hotel {
+            "PENDING"
+        } else {
+            "COMPLETED"
+        }
+    }
+
+    /// Returns the webservices holding a booking of this (pending) request because
+    /// their dispatcher is paused.
+
+    pub fn held_by(&self) -> Vec<String> {
+        if self.completed_at.is_some() {
+            return Vec::new();
+        }
+
+        // A booking is held from its last Held event until it is released or fetched
+        let mut held = BTreeMap::new();
+        for event in &self.events {
+            if let Some(webservice) = &event.webservice {
+                let booking = (webservice, event.leg);
+                match event.kind {
+                    EventKind::Held => held.insert(booking, true),
+                    EventKind::Released | EventKind::FetchStarted => held.insert(booking, false),
+                    _ => None,
+                };
+            }
+        }
+        held.into_iter()
+            .filter(|(_, is_held)| *is_held)
+            .map(|((webservice, _), _)| webservice.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Returns the bookings already made of the request: the legs (by index) and the hotel (no leg).
+
+    fn booked(&self) -> Vec<Option<usize>> {
+        let n_legs = self.req.raw_request.legs().len();
+        let mut booked = (0..n_legs)
+            .filter(|leg| !self.pending_legs.contains(leg))
+            .map(Some)
+            .collect::<Vec<_>>();
+        if self.req.raw_request.package && !self.pending_hotel {
+            booked.push(None);
+        }
+        booked
+    }
+
+    /// Marks the given itinerary leg as no longer pending.
+
+    fn book_leg(&mut self, leg: Option<usize>) {
+        self.pending_legs
+            .retain(|pending_leg| Some(*pending_leg) != leg);
+    }
+}
+
 // ACTOR ----------------------------------------------------------------------
 
 /// RequestStatus is an entity <Actor>. It will be in charge of collecting
 /// finished status for all requests. It will also be communicating with Log and Metrics Actors.
+/// Statuses are kept in the StatusStore of the configured backend, which may evict the
+/// completed requests (once their webhook, if any, was delivered). The bookings made of a
+/// rejected request are released through the dispatchers registered by the RequestHandler.
 
 pub struct StatusService {
-    reqs: HashMap<String, RequestStatus>,
+    store: Box<dyn StatusStore>,
+    delivering: HashSet<String>,
+    airlines: Airlines,
+    hotel: Option<Hotel>,
+    eviction_period: u64,
     logger: Addr<Logger>,
     metrics_collector: Addr<MetricsCollector>,
+    offers: Addr<OffersService>,
+    webhooks: Addr<WebhookService>,
 }
 
 impl StatusService {
-    pub fn new(logger: Addr<Logger>, metrics_collector: Addr<MetricsCollector>) -> Self {
+    /// Given a StatusServiceConfig, an Addr Logger, an Addr MetricsCollector, an Addr OffersService and
+    /// an Addr WebhookService this method will create a StatusService entity, opening its store.
+
+    pub fn new(
+        config: StatusServiceConfig,
+        logger: Addr<Logger>,
+        metrics_collector: Addr<MetricsCollector>,
+        offers: Addr<OffersService>,
+        webhooks: Addr<WebhookService>,
+    ) -> Self {
+        let eviction_period = config.eviction_period;
+        let mut store = store::from_config(config)
+            .unwrap_or_else(|err| panic!("[CRITICAL] Error while opening store: {}", err));
+        match store.fail_pending(now(), INTERRUPTED_REASON) {
+            Ok(0) => {}
+            Ok(n_failed) => Logger::send_to(
+                &logger,
+                format!(
+                    "[StatusService] [WARNING] Rejected {} requests left pending by the previous run",
+                    n_failed
+                ),
+            ),
+            Err(err) => Logger::send_to(
+                &logger,
+                format!(
+                    "[StatusService] [WARNING] Could not reject requests left pending: {}",
+                    err
+                ),
+            ),
+        }
+
         StatusService {
-            reqs: HashMap::<String, RequestStatus>::new(),
+            store,
+            delivering: HashSet::new(),
+            airlines: Airlines::new(),
+            hotel: None,
+            eviction_period,
             logger,
             metrics_collector,
+            offers,
+            webhooks,
+        }
+    }
+
+    /// Given a StatusService addr this method is used to record a webhook delivery attempt of a request.
+
+    pub fn record_delivery(
+        status_service: &Addr<StatusService>,
+        req_id: String,
+        attempt: DeliveryAttempt,
+    ) {
+        // Waits for room in the mailbox: the request is kept while its delivery is in progress
+        let status_service = status_service.clone();
+        actix::spawn(async move {
+            if status_service
+                .send(RecordDelivery { req_id, attempt })
+                .await
+                .is_err()
+            {
+                println!("Warning: failed to send delivery attempt to StatusService");
+            }
+        });
+    }
+
+    fn get(&self, req_id: &str) -> Option<RequestStatus> {
+        match self.store.get(req_id) {
+            Ok(req_status) => req_status,
+            Err(err) => {
+                Logger::send_to(
+                    &self.logger,
+                    format!(
+                        "[StatusService] [WARNING] Could not read status of request {}: {}",
+                        req_id, err
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    fn put(&mut self, req_status: &RequestStatus) {
+        if let Err(err) = self.store.put(req_status) {
+            Logger::send_to(
+                &self.logger,
+                format!(
+                    "[StatusService] [WARNING] Could not store status of request {}: {}",
+                    req_status.req.id, err
+                ),
+            );
+        }
+    }
+
+    /// Posts the final status of a completed request to its callback URL, if any.
+
+    fn notify(&mut self, req_status: &RequestStatus, addr: Addr<StatusService>) {
+        let callback_url = match &req_status.req.raw_request.callback_url {
+            Some(callback_url) => callback_url.clone(),
+            None => return,
+        };
+        let req_id = &req_status.req.id;
+        let correlation_id = req_status.req.correlation_id.clone();
+
+        match serde_json::to_string(&StatusResponse::from(req_status.clone())) {
+            Ok(body) => {
+                self.delivering.insert(req_id.clone());
+                WebhookService::deliver(
+                    &self.webhooks,
+                    req_id.clone(),
+                    correlation_id,
+                    callback_url,
+                    body,
+                    addr,
+                )
+            }
+            Err(err) => Logger::send_to(
+                &self.logger,
+                format!(
+                    "[StatusService] [WARNING] Could not serialize status of request {}: {}",
+                    req_id, err
+                ),
+            ),
+        }
+    }
+
+    /// Releases a booking made of a rejected request, through the dispatcher of its webservice.
+
+    fn release(&self, booking: Booking) {
+        let dispatcher = match booking.leg {
+            Some(leg) => booking
+                .req
+                .raw_request
+                .legs()
+                .into_iter()
+                .nth(leg)
+                .and_then(|leg| self.airlines.get(&leg.airline)),
+            None => self.hotel.as_ref(),
+        };
+        let dispatcher: Addr<WebServiceDispatcher> = match dispatcher {
+            Some(dispatcher) => dispatcher.clone(),
+            None => {
+                Logger::send_to(
+                    &self.logger,
+                    format!(
+                        "[StatusService] [WARNING] No dispatcher to release booking of request {}",
+                        booking.req
+                    ),
+                );
+                return;
+            }
+        };
+
+        let logger = self.logger.clone();
+        actix::spawn(async move {
+            if let Err(err) = dispatcher.send(ReleaseBooking { booking }).await {
+                Logger::send_to(
+                    &logger,
+                    format!(
+                        "[StatusService] [WARNING] Could not release booking: {}",
+                        err
+                    ),
+                );
+            }
+        });
+    }
+
+    fn evict_completed(&mut self) {
+        // Requests are kept while their webhook is delivered, to record every attempt
+        match self.store.evict_completed(now(), &self.delivering) {
+            Ok(0) => {}
+            Ok(n_evicted) => Logger::send_to(
+                &self.logger,
+                format!("[StatusService] Evicted {} completed requests", n_evicted),
+            ),
+            Err(err) => Logger::send_to(
+                &self.logger,
+                format!(
+                    "[StatusService] [WARNING] Could not evict completed requests: {}",
+                    err
+                ),
+            ),
         }
     }
 }
@@ -59,13 +484,34 @@ impl StatusService {
 impl Actor for StatusService {
     type Context = Context<Self>;
 
-    fn started(&mut self, _: &mut Self::Context) {
-        Logger::send_to(&self.logger, "[StatusService] Started".to_string());
+    fn started(&mut self, ctx: &mut Self::Context) {
+        Logger::send_to(
+            &self.logger,
+            format!("[StatusService] Started ({} store)", self.store.name()),
+        );
+        ctx.run_interval(Duration::from_millis(self.eviction_period), |me, _ctx| {
+            me.evict_completed()
+        });
+    }
+}
+
+impl Supervised for StatusService {
+    fn restarting(&mut self, _ctx: &mut Context<Self>) {
+        Logger::send_to(&self.logger, "[StatusService] Restarting".to_string());
     }
 }
 
 // MESSAGES -------------------------------------------------------------------
 
+/// Message to register the dispatchers of every airline and the hotel, to release
+/// the bookings of rejected requests.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RegisterDispatchers {
+    pub airlines: Airlines,
+    pub hotel: Hotel,
+}
+
 /// Message that indicates a new request started.
 #[derive(Message)]
 #[rtype(result = "()")]
@@ -73,12 +519,37 @@ pub struct NewRequest {
     pub req: Request,
 }
 
+/// Message to record an event in the timeline of a request.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RecordEvent {
+    pub req_id: String,
+    pub event: RequestEvent,
+}
+
+/// Message to record a webhook delivery attempt of a request.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RecordDelivery {
+    pub req_id: String,
+    pub attempt: DeliveryAttempt,
+}
+
 /// Message that indicates that a webservice book succeded.
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct BookSucceeded {
     pub book_type: WebServiceType,
-    pub req: Request,
+    pub booking: Booking,
+}
+
+/// Message that indicates that a webservice definitively rejected a book.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct BookRejected {
+    pub book_type: WebServiceType,
+    pub booking: Booking,
+    pub reason: String,
 }
 
 /// Message to get request finish status.
@@ -90,71 +561,215 @@ pub struct GetStatus {
 
 // HANDLERS -------------------------------------------------------------------
 
+impl Handler<RegisterDispatchers> for StatusService {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        RegisterDispatchers { airlines, hotel }: RegisterDispatchers,
+        _ctx: &mut Context<Self>,
+    ) {
+        self.airlines = airlines;
+        self.hotel = Some(hotel);
+    }
+}
+
 impl Handler<NewRequest> for StatusService {
     type Result = ();
 
     fn handle(&mut self, NewRequest { req }: NewRequest, _ctx: &mut Context<Self>) {
-        let req_id = req.id.clone();
-        self.reqs.insert(req_id.clone(), RequestStatus::new(req));
         Logger::send_to(
             &self.logger,
-            format!("[StatusService] Registered request {}", req_id),
+            format!("[StatusService] Registered request {}", req),
         );
+        self.put(&RequestStatus::new(req));
     }
 }
 
-impl Handler<BookSucceeded> for StatusService {
+impl Handler<RecordEvent> for StatusService {
+    type Result = ();
+
+    fn handle(&mut self, RecordEvent { req_id, event }: RecordEvent, _ctx: &mut Context<Self>) {
+        match self.get(&req_id) {
+            Some(mut req_status) => {
+                req_status.events.push(event);
+                self.put(&req_status);
+            }
+            None => Logger::send_to(
+                &self.logger,
+                format!(
+                    "[StatusService] [WARNING] Dropped {:?} event of evicted request {}",
+                    event.kind, req_id
+                ),
+            ),
+        }
+    }
+}
+
+impl Handler<RecordDelivery> for StatusService {
     type Result = ();
 
     fn handle(
         &mut self,
-        BookSucceeded { req, book_type }: BookSucceeded,
+        RecordDelivery { req_id, attempt }: RecordDelivery,
         _ctx: &mut Context<Self>,
     ) {
-        let req_status = self
-            .reqs
-            .get_mut(&req.id)
-            .expect("[CRITICAL] StatusService received BookSucceeded of unregistered request");
+        if attempt.retry_in.is_none() {
+            self.delivering.remove(&req_id);
+        }
+        match self.get(&req_id) {
+            Some(mut req_status) => {
+                req_status.deliveries.push(attempt);
+                self.put(&req_status);
+            }
+            None => Logger::send_to(
+                &self.logger,
+                format!(
+                    "[StatusService] [WARNING] Dropped delivery attempt {} of evicted request {}",
+                    attempt.attempt, req_id
+                ),
+            ),
+        }
+    }
+}
 
-        match book_type {
-            WebServiceType::Airline => {
+impl Handler<BookSucceeded> for StatusService {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        BookSucceeded { booking, book_type }: BookSucceeded,
+        ctx: &mut Context<Self>,
+    ) {
+        let req = &booking.req;
+        let mut req_status = match self.get(&req.id) {
+            Some(req_status) => req_status,
+            None => {
+                // Late result of a request already rejected and evicted (or never registered)
                 Logger::send_to(
                     &self.logger,
                     format!(
-                        "[StatusService] Airline book registered for request {}",
+                        "[StatusService] [WARNING] Dropped BookSucceeded of evicted request {}",
                         req.id
                     ),
                 );
-                req_status.pending_airline = false;
+                return;
             }
-            WebServiceType::Hotel => {
+        };
+
+        match book_type {
+            WebServiceType::Airline => {
                 Logger::send_to(
                     &self.logger,
                     format!(
-                        "[StatusService] Hotel book registered for request {}",
-                        req.id
+                        "[StatusService] Airline book registered for request {} (leg {})",
+                        req,
+                        booking.leg.unwrap_or_default()
                     ),
                 );
+                req_status.book_leg(booking.leg);
+            }
+            WebServiceType::Hotel => {
+                Logger::send_to(
+                    &self.logger,
+                    format!("[StatusService] Hotel book registered for request {}", req),
+                );
                 req_status.pending_hotel = false;
             }
         }
 
-        if !req_status.pending_hotel && !req_status.pending_airline {
+        // A booking made after its request was rejected is released right away
+        if req_status.rejection.is_some() {
+            self.release(booking.clone());
+        }
+
+        let completed = req_status.completed_at.is_none()
+            && !req_status.pending_hotel
+            && req_status.pending_legs.is_empty();
+        if completed {
+            req_status.completed_at = Some(now());
+            req_status
+                .events
+                .push(RequestEvent::new(EventKind::Completed));
+        }
+        self.put(&req_status);
+
+        if completed {
             Logger::send_to(
                 &self.logger,
-                format!("[StatusService] Finished request {}", req.id),
+                format!("[StatusService] Finished request {}", req),
             );
             MetricsCollector::collect(
                 &self.metrics_collector,
                 req.start_time,
                 now(),
-                req.raw_request.origin,
-                req.raw_request.destiny,
+                req.raw_request.booked_routes(),
+                req.raw_request.party_size(),
             );
+            if let Some(offer_id) = &req.raw_request.offer_id {
+                OffersService::collect_booking(&self.offers, offer_id.clone());
+            }
+            self.notify(&req_status, ctx.address());
         }
     }
 }
 
+impl Handler<BookRejected> for StatusService {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        BookRejected {
+            book_type,
+            booking,
+            reason,
+        }: BookRejected,
+        ctx: &mut Context<Self>,
+    ) {
+        let req = &booking.req;
+        let mut req_status = match self.get(&req.id) {
+            Some(req_status) => req_status,
+            None => {
+                // Late result of a request already rejected and evicted (or never registered)
+                Logger::send_to(
+                    &self.logger,
+                    format!(
+                        "[StatusService] [WARNING] Dropped BookRejected of evicted request {}",
+                        req.id
+                    ),
+                );
+                return;
+            }
+        };
+
+        let booked = req_status.booked();
+        match book_type {
+            WebServiceType::Airline => req_status.book_leg(booking.leg),
+            WebServiceType::Hotel => req_status.pending_hotel = false,
+        }
+        if req_status.completed_at.is_some() {
+            self.put(&req_status);
+            return;
+        }
+
+        req_status.rejection = Some(reason.clone());
+        req_status.completed_at = Some(now());
+        self.put(&req_status);
+        Logger::send_to(
+            &self.logger,
+            format!("[StatusService] Rejected request {}: {}", req, reason),
+        );
+        MetricsCollector::collect_rejected(&self.metrics_collector);
+        for leg in booked {
+            self.release(Booking {
+                req: req.clone(),
+                leg,
+            });
+        }
+        self.notify(&req_status, ctx.address());
+    }
+}
+
 impl Handler<GetStatus> for StatusService {
     type Result = Result<RequestStatus, StatusServiceError>;
 
@@ -163,16 +778,236 @@ impl Handler<GetStatus> for StatusService {
         GetStatus { req_id }: GetStatus,
         _ctx: &mut Context<Self>,
     ) -> Result<RequestStatus, StatusServiceError> {
-        let req = self
-            .reqs
-            .get(&req_id)
-            .ok_or(StatusServiceError::RequestNotFound)?;
+        match self.get(&req_id) {
+            Some(req_status) => {
+                Logger::send_to(
+                    &self.logger,
+                    format!("[StatusService] Retrieved status for request {}", req_id),
+                );
+                Ok(req_status)
+            }
+            None => Err(StatusServiceError::RequestNotFound),
+        }
+    }
+}
 
-        Logger::send_to(
-            &self.logger,
-            format!("[StatusService] Retrieved status for request {}", req_id),
-        );
+impl Handler<Ping> for StatusService {
+    type Result = Pong;
+
+    fn handle(&mut self, _msg: Ping, _ctx: &mut Context<Self>) -> Self::Result {
+        Pong {
+            queue_depth: Some(self.delivering.len()),
+            paused: None,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::common::{
+        config::{
+            ArchiveConfig, LoggerConfig, MetricsCollectorConfig, OffersConfig, StoreBackend,
+            StoreConfig, WebhooksConfig,
+        },
+        utils::uuid,
+    };
+
+    pub(crate) fn temp_dir() -> String {
+        let dirpath = env::temp_dir()
+            .join(format!("tp1-status-{}-{}", process::id(), uuid()))
+            .to_string_lossy()
+            .to_string();
+        fs::create_dir_all(&dirpath).expect("Could not create temp dir");
+        dirpath
+    }
+
+    /// Request for a two legs itinerary: EZE -> MAD (Iberia) -> CDG (Iberia).
+    pub(crate) fn request(id: &str) -> Request {
+        Request {
+            id: String::from(id),
+            start_time: now(),
+            raw_request: RawRequest {
+                origin: String::from("EZE"),
+                destiny: String::from("CDG"),
+                airline: String::from("Iberia"),
+                package: false,
+                stops: vec![Stop {
+                    airport: String::from("MAD"),
+                    airline: String::from("Iberia"),
+                }],
+                round_trip: false,
+                departure_date: None,
+                return_date: None,
+                return_airline: None,
+                passengers: Vec::new(),
+                offer_id: None,
+                callback_url: None,
+            },
+            correlation_id: None,
+        }
+    }
+
+    pub(crate) fn start_logger_and_metrics(
+        dirpath: &str,
+    ) -> (Addr<Logger>, Addr<MetricsCollector>) {
+        let logger = Logger::new(LoggerConfig {
+            dirpath: dirpath.to_string(),
+        })
+        .start();
+        let metrics_collector = MetricsCollector::new(
+            MetricsCollectorConfig {
+                printer_period: 60000,
+                n_most_booked: 3,
+            },
+            logger.clone(),
+        )
+        .start();
+        (logger, metrics_collector)
+    }
+
+    pub(crate) fn start_status_service(
+        dirpath: &str,
+        backend: StoreBackend,
+    ) -> Addr<StatusService> {
+        let (logger, metrics_collector) = start_logger_and_metrics(dirpath);
+        let offers = OffersService::new(
+            OffersConfig {
+                refresh_period: 60000,
+                validity: 60000,
+                tiers: Vec::new(),
+            },
+            logger.clone(),
+            metrics_collector.clone(),
+        )
+        .start();
+        let webhooks = WebhookService::new(
+            WebhooksConfig {
+                secret: String::from("secret"),
+                max_attempts: 1,
+                initial_backoff: 10,
+                max_backoff: 10,
+                timeout: 10,
+            },
+            logger.clone(),
+        )
+        .start();
+        let config = StatusServiceConfig {
+            retention_ttl: 60000,
+            max_entries: 100,
+            eviction_period: 60000,
+            archive_config: ArchiveConfig {
+                dirpath: dirpath.to_string(),
+                max_file_size: 1024 * 1024,
+                max_files: 2,
+            },
+            store_config: StoreConfig {
+                backend,
+                path: format!("{}/status.db", dirpath),
+            },
+        };
+
+        StatusService::new(config, logger, metrics_collector, offers, webhooks).start()
+    }
+
+    pub(crate) async fn get_status(
+        status_service: &Addr<StatusService>,
+        req_id: &str,
+    ) -> Option<RequestStatus> {
+        status_service
+            .send(GetStatus {
+                req_id: req_id.to_string(),
+            })
+            .await
+            .expect("StatusService unavailable")
+            .ok()
+    }
+
+    async fn book(status_service: &Addr<StatusService>, req: &Request, leg: usize, rejected: bool) {
+        let booking = Booking {
+            req: req.clone(),
+            leg: Some(leg),
+        };
+        let book_type = WebServiceType::Airline;
+        let sent = match rejected {
+            true => {
+                status_service
+                    .send(BookRejected {
+                        book_type,
+                        booking,
+                        reason: String::from("sold out"),
+                    })
+                    .await
+            }
+            false => {
+                status_service
+                    .send(BookSucceeded { book_type, booking })
+                    .await
+            }
+        };
+        sent.expect("StatusService unavailable");
+    }
+
+    /// Runs a completed and a rejected request through the handlers, on the given backend.
+    async fn assert_handlers(backend: StoreBackend) {
+        let dirpath = temp_dir();
+        let status_service = start_status_service(&dirpath, backend);
+
+        let completed = request("completed");
+        status_service
+            .send(NewRequest {
+                req: completed.clone(),
+            })
+            .await
+            .expect("StatusService unavailable");
+        book(&status_service, &completed, 0, false).await;
+        let req_status = get_status(&status_service, "completed")
+            .await
+            .expect("completed not found");
+        assert_eq!(req_status.status(), "PENDING");
+        assert_eq!(req_status.pending_legs, vec![1]);
+        book(&status_service, &completed, 1, false).await;
+        let req_status = get_status(&status_service, "completed")
+            .await
+            .expect("completed not found");
+        assert_eq!(req_status.status(), "COMPLETED");
+        assert!(matches!(
+            req_status.events.last().map(|event| event.kind),
+            Some(EventKind::Completed)
+        ));
+
+        // The late result of its other leg does not complete a rejected request
+        let rejected = request("rejected");
+        status_service
+            .send(NewRequest {
+                req: rejected.clone(),
+            })
+            .await
+            .expect("StatusService unavailable");
+        book(&status_service, &rejected, 1, true).await;
+        book(&status_service, &rejected, 0, false).await;
+        let req_status = get_status(&status_service, "rejected")
+            .await
+            .expect("rejected not found");
+        assert_eq!(req_status.status(), "REJECTED");
+        assert_eq!(req_status.rejection.as_deref(), Some("sold out"));
+
+        // Results of unknown requests are dropped
+        book(&status_service, &request("unknown"), 0, false).await;
+        assert!(get_status(&status_service, "unknown").await.is_none());
+
+        fs::remove_dir_all(dirpath).ok();
+    }
+
+    #[actix_web::test]
+    async fn handlers_with_memory_store() {
+        assert_handlers(StoreBackend::Memory).await;
+    }
 
-        Ok(req.clone())
+    #[actix_web::test]
+    async fn handlers_with_sqlite_store() {
+        assert_handlers(StoreBackend::Sqlite).await;
     }
 }
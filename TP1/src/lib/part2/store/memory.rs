@@ -0,0 +1,118 @@
+//! In-memory store, backed by the rolling archive.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::common::config::ArchiveConfig;
+use crate::part2::{
+    archive::StatusArchive,
+    status_service::RequestStatus,
+    store::{StatusStore, StoreResult},
+};
+
+/// MemoryStore keeps the statuses in memory. Completed requests are kept for
+/// `retention_ttl` ms (and up to `max_entries`), after that they are evicted
+/// to the StatusArchive, where they can still be looked up.
+
+pub struct MemoryStore {
+    reqs: HashMap<String, RequestStatus>,
+    completed: VecDeque<String>,
+    retention_ttl: i64,
+    max_entries: usize,
+    archive: StatusArchive,
+}
+
+impl MemoryStore {
+    /// Given the retention rules and an ArchiveConfig this method will create
+    /// an empty MemoryStore, opening its archive.
+
+    pub fn new(
+        retention_ttl: i64,
+        max_entries: usize,
+        archive_config: ArchiveConfig,
+    ) -> StoreResult<Self> {
+        Ok(MemoryStore {
+            reqs: HashMap::new(),
+            completed: VecDeque::new(),
+            retention_ttl,
+            max_entries,
+            archive: StatusArchive::new(archive_config)?,
+        })
+    }
+
+    fn evict(&mut self, req_id: &str) -> StoreResult<()> {
+        match self.reqs.remove(req_id) {
+            Some(req_status) => Ok(self.archive.append(&req_status)?),
+            None => Ok(()),
+        }
+    }
+}
+
+impl StatusStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn put(&mut self, req_status: &RequestStatus) -> StoreResult<()> {
+        let req_id = &req_status.req.id;
+        // Updates of archived requests (e.g. a late delivery attempt) replace their archived status
+        if !self.reqs.contains_key(req_id) && self.archive.contains(req_id) {
+            return Ok(self.archive.append(req_status)?);
+        }
+
+        let was_completed = self
+            .reqs
+            .get(req_id)
+            .is_some_and(|previous| previous.completed_at.is_some());
+
+        // Completed ids are pushed in completion order, so the oldest ones are at the front
+        if req_status.completed_at.is_some() && !was_completed {
+            self.completed.push_back(req_id.clone());
+        }
+        self.reqs.insert(req_id.clone(), req_status.clone());
+        Ok(())
+    }
+
+    fn get(&self, req_id: &str) -> StoreResult<Option<RequestStatus>> {
+        match self.reqs.get(req_id) {
+            Some(req_status) => Ok(Some(req_status.clone())),
+            None => Ok(self.archive.lookup(req_id)?),
+        }
+    }
+
+    fn evict_completed(&mut self, now: i64, keep: &HashSet<String>) -> StoreResult<usize> {
+        let mut n_evicted = 0;
+        let mut kept = Vec::new();
+        let mut result = Ok(());
+
+        // Kept requests are skipped (not evicted yet), without blocking the ones behind them
+        while let Some(req_id) = self.completed.pop_front() {
+            if keep.contains(&req_id) {
+                kept.push(req_id);
+                continue;
+            }
+            let expired = match self
+                .reqs
+                .get(&req_id)
+                .and_then(|status| status.completed_at)
+            {
+                Some(completed_at) => now - completed_at >= self.retention_ttl,
+                None => true,
+            };
+            if !expired && self.completed.len() + kept.len() < self.max_entries {
+                self.completed.push_front(req_id);
+                break;
+            }
+
+            if let Err(err) = self.evict(&req_id) {
+                result = Err(err);
+                break;
+            }
+            n_evicted += 1;
+        }
+
+        for req_id in kept.into_iter().rev() {
+            self.completed.push_front(req_id);
+        }
+        result.map(|_| n_evicted)
+    }
+}
@@ -0,0 +1,342 @@
+//! Storage of the request statuses.
+//!
+//! The StatusService keeps the status of every request in a StatusStore, chosen
+//! by the `store_config` backend:
+//! * `memory`: statuses are kept in memory while the service runs, and completed
+//!   requests are evicted to the rolling StatusArchive after `retention_ttl` ms.
+//! * `sqlite`: statuses are kept in an embedded SQLite database, indexed by status,
+//!   airline and route, so the whole history can be queried after a restart. Requests
+//!   left pending by the previous run are rejected when it is opened.
+
+mod memory;
+mod sqlite;
+
+use std::{collections::HashSet, fmt, io};
+
+use crate::common::config::{StatusServiceConfig, StoreBackend, StoreConfig};
+use crate::part2::status_service::RequestStatus;
+
+pub use memory::MemoryStore;
+pub use sqlite::SqliteStore;
+
+// TYPES ----------------------------------------------------------------------
+
+/// Error of a StatusStore operation.
+#[derive(Debug)]
+pub struct StoreError(String);
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+/// Storage of the request statuses, by request id.
+pub trait StatusStore {
+    /// Name of the backend, to be logged.
+    fn name(&self) -> &'static str;
+
+    /// Stores the status of a request, replacing its previous one.
+    fn put(&mut self, req_status: &RequestStatus) -> StoreResult<()>;
+
+    /// Returns the status of a request, if it was ever stored.
+    fn get(&self, req_id: &str) -> StoreResult<Option<RequestStatus>>;
+
+    /// Releases the completed requests that should no longer be kept (except the
+    /// ones in `keep`), returning how many were released. Nothing by default.
+    fn evict_completed(&mut self, _now: i64, _keep: &HashSet<String>) -> StoreResult<usize> {
+        Ok(0)
+    }
+
+    /// Rejects (with the given reason) the requests left pending by a previous run, as
+    /// they will never be completed, returning how many were rejected. Nothing by default.
+    fn fail_pending(&mut self, _now: i64, _reason: &str) -> StoreResult<usize> {
+        Ok(0)
+    }
+}
+
+// FUNCTIONS ------------------------------------------------------------------
+
+/// Given a StatusServiceConfig this function opens the store of its backend.
+
+pub fn from_config(
+    StatusServiceConfig {
+        retention_ttl,
+        max_entries,
+        archive_config,
+        store_config: StoreConfig { backend, path },
+        ..
+    }: StatusServiceConfig,
+) -> StoreResult<Box<dyn StatusStore>> {
+    let store: Box<dyn StatusStore> = match backend {
+        StoreBackend::Memory => Box::new(MemoryStore::new(
+            retention_ttl as i64,
+            max_entries,
+            archive_config,
+        )?),
+        StoreBackend::Sqlite => Box::new(SqliteStore::open(&path)?),
+    };
+    Ok(store)
+}
+
+// ERRORS ---------------------------------------------------------------------
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(err: io::Error) -> Self {
+        StoreError(err.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        StoreError(format!("sqlite: {}", err))
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(err: serde_json::Error) -> Self {
+        StoreError(format!("invalid status: {}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::common::{config::ArchiveConfig, request::RawRequest, utils::uuid};
+    use crate::part2::{archive::StatusArchive, request::Request};
+
+    fn temp_dir() -> String {
+        let dirpath = env::temp_dir()
+            .join(format!("tp1-store-{}-{}", process::id(), uuid()))
+            .to_string_lossy()
+            .to_string();
+        fs::create_dir_all(&dirpath).expect("Could not create temp dir");
+        dirpath
+    }
+
+    fn req_status(id: &str) -> RequestStatus {
+        RequestStatus::new(Request {
+            id: String::from(id),
+            start_time: 0,
+            raw_request: RawRequest {
+                origin: String::from("EZE"),
+                destiny: String::from("MAD"),
+                airline: String::from("Iberia"),
+                package: false,
+                stops: Vec::new(),
+                round_trip: false,
+                departure_date: None,
+                return_date: None,
+                return_airline: None,
+                passengers: Vec::new(),
+                offer_id: None,
+                callback_url: None,
+            },
+            correlation_id: None,
+        })
+    }
+
+    fn completed(id: &str, completed_at: i64) -> RequestStatus {
+        let mut req_status = req_status(id);
+        req_status.pending_legs.clear();
+        req_status.completed_at = Some(completed_at);
+        req_status
+    }
+
+    fn memory_store(dirpath: &str) -> MemoryStore {
+        let archive_config = ArchiveConfig {
+            dirpath: dirpath.to_string(),
+            max_file_size: 1024 * 1024,
+            max_files: 2,
+        };
+        MemoryStore::new(100, 10, archive_config).expect("Could not open memory store")
+    }
+
+    fn sqlite_store(dirpath: &str) -> SqliteStore {
+        SqliteStore::open(&format!("{}/status.db", dirpath)).expect("Could not open sqlite store")
+    }
+
+    /// Status lifecycle every backend must support, as the StatusService handlers use it.
+    fn assert_lifecycle(store: &mut dyn StatusStore) {
+        assert!(store.get("a").expect("get failed").is_none());
+
+        store.put(&req_status("a")).expect("put failed");
+        let stored = store.get("a").expect("get failed").expect("a not stored");
+        assert_eq!(stored.status(), "PENDING");
+
+        store.put(&completed("a", 10)).expect("put failed");
+        let stored = store.get("a").expect("get failed").expect("a not stored");
+        assert_eq!(stored.status(), "COMPLETED");
+        assert_eq!(stored.completed_at, Some(10));
+    }
+
+    #[test]
+    fn memory_store_lifecycle() {
+        let dirpath = temp_dir();
+        assert_lifecycle(&mut memory_store(&dirpath));
+        fs::remove_dir_all(dirpath).ok();
+    }
+
+    #[test]
+    fn sqlite_store_lifecycle() {
+        let dirpath = temp_dir();
+        assert_lifecycle(&mut sqlite_store(&dirpath));
+        fs::remove_dir_all(dirpath).ok();
+    }
+
+    #[test]
+    fn memory_store_evicts_to_archive() {
+        let dirpath = temp_dir();
+        let mut store = memory_store(&dirpath);
+        store.put(&completed("a", 0)).expect("put failed");
+        store.put(&completed("b", 0)).expect("put failed");
+        store.put(&completed("c", 50)).expect("put failed");
+        store.put(&completed("d", 90)).expect("put failed");
+
+        // A kept request does not block the eviction of the ones completed after it
+        let keep: HashSet<String> = vec![String::from("b")].into_iter().collect();
+        assert_eq!(store.evict_completed(150, &keep).expect("evict failed"), 2);
+        assert!(
+            store.get("a").expect("get failed").is_some(),
+            "a not archived"
+        );
+        assert!(
+            store.get("c").expect("get failed").is_some(),
+            "c not archived"
+        );
+        assert_eq!(
+            store
+                .evict_completed(150, &HashSet::new())
+                .expect("evict failed"),
+            1
+        );
+        assert_eq!(
+            store
+                .evict_completed(200, &HashSet::new())
+                .expect("evict failed"),
+            1
+        );
+        assert!(
+            store.get("d").expect("get failed").is_some(),
+            "d not archived"
+        );
+        fs::remove_dir_all(dirpath).ok();
+    }
+
+    #[test]
+    fn memory_store_updates_archived_statuses() {
+        let dirpath = temp_dir();
+        let mut store = memory_store(&dirpath);
+        store.put(&completed("a", 0)).expect("put failed");
+        assert_eq!(
+            store
+                .evict_completed(100, &HashSet::new())
+                .expect("evict failed"),
+            1
+        );
+
+        // A late update is archived right away, not queued to be evicted again
+        let mut late = completed("a", 0);
+        late.rejection = Some(String::from("late"));
+        store.put(&late).expect("put failed");
+        assert_eq!(
+            store
+                .evict_completed(100, &HashSet::new())
+                .expect("evict failed"),
+            0
+        );
+        let archived = store.get("a").expect("get failed").expect("a not archived");
+        assert_eq!(archived.rejection.as_deref(), Some("late"));
+
+        // The index is rebuilt when the archive is opened again
+        let store = memory_store(&dirpath);
+        let archived = store.get("a").expect("get failed").expect("a not archived");
+        assert_eq!(archived.rejection.as_deref(), Some("late"));
+        fs::remove_dir_all(dirpath).ok();
+    }
+
+    #[test]
+    fn archive_lookups_follow_rotations() {
+        let dirpath = temp_dir();
+        let archive_config = ArchiveConfig {
+            dirpath: dirpath.clone(),
+            max_file_size: 1,
+            max_files: 2,
+        };
+        // Every status fills a whole file, so each append rotates
+        let mut archive = StatusArchive::new(archive_config).expect("Could not open archive");
+        for id in ["a", "b", "c"] {
+            archive.append(&completed(id, 0)).expect("append failed");
+        }
+
+        assert!(archive.lookup("a").expect("lookup failed").is_none());
+        for id in ["b", "c"] {
+            let archived = archive.lookup(id).expect("lookup failed");
+            assert_eq!(archived.map(|status| status.req.id), Some(String::from(id)));
+        }
+        fs::remove_dir_all(dirpath).ok();
+    }
+
+    #[test]
+    fn memory_store_caps_entries_past_kept_requests() {
+        let dirpath = temp_dir();
+        let mut store = memory_store(&dirpath);
+        for i in 0..12 {
+            store
+                .put(&completed(&format!("r{}", i), 0))
+                .expect("put failed");
+        }
+
+        // Nothing is expired yet, but only max_entries (10) completed requests are kept
+        let keep: HashSet<String> = vec![String::from("r0")].into_iter().collect();
+        assert_eq!(store.evict_completed(0, &keep).expect("evict failed"), 2);
+        assert_eq!(
+            store
+                .evict_completed(0, &HashSet::new())
+                .expect("evict failed"),
+            0
+        );
+        fs::remove_dir_all(dirpath).ok();
+    }
+
+    #[test]
+    fn sqlite_store_keeps_history_after_reopening() {
+        let dirpath = temp_dir();
+        {
+            let mut store = sqlite_store(&dirpath);
+            store.put(&completed("a", 10)).expect("put failed");
+            store.put(&req_status("b")).expect("put failed");
+            assert_eq!(
+                store
+                    .evict_completed(1000, &HashSet::new())
+                    .expect("evict failed"),
+                0
+            );
+        }
+
+        let mut store = sqlite_store(&dirpath);
+        assert_eq!(
+            store.get("a").expect("get failed").map(|s| s.completed_at),
+            Some(Some(10))
+        );
+        assert!(store.get("b").expect("get failed").is_some());
+        assert_eq!(store.count_by_status("PENDING").expect("count failed"), 1);
+
+        // The request left pending will never complete
+        assert_eq!(
+            store
+                .fail_pending(2000, "interrupted")
+                .expect("fail pending failed"),
+            1
+        );
+        let b = store.get("b").expect("get failed").expect("b not stored");
+        assert_eq!(b.status(), "REJECTED");
+        assert_eq!(b.completed_at, Some(2000));
+        assert_eq!(store.count_by_status("PENDING").expect("count failed"), 0);
+        fs::remove_dir_all(dirpath).ok();
+    }
+}
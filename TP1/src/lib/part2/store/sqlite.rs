@@ -0,0 +1,138 @@
+//! Embedded SQLite store.
+
+use std::{fs, path::Path};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::part2::{
+    status_service::{EventKind, RequestEvent, RequestStatus},
+    store::{StatusStore, StoreResult},
+};
+
+// CONSTANTS ------------------------------------------------------------------
+
+/// Every status update is a write, run within the StatusService: with a write-ahead log
+/// synced only at checkpoints, writes don't wait for a disk flush each.
+const PRAGMAS: &str = "
+    PRAGMA journal_mode = WAL;
+    PRAGMA synchronous = NORMAL;
+";
+
+/// Every status is kept as JSON (`data`), along with the indexed columns to query them.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS request_status (
+        id TEXT PRIMARY KEY,
+        status TEXT NOT NULL,
+        airline TEXT NOT NULL,
+        origin TEXT NOT NULL,
+        destiny TEXT NOT NULL,
+        start_time INTEGER NOT NULL,
+        completed_at INTEGER,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS request_status_status ON request_status (status);
+    CREATE INDEX IF NOT EXISTS request_status_airline ON request_status (airline);
+    CREATE INDEX IF NOT EXISTS request_status_route ON request_status (origin, destiny);
+";
+
+// ENTITY ---------------------------------------------------------------------
+
+/// SqliteStore keeps the statuses in a SQLite database, that is never evicted:
+/// the whole request history survives restarts.
+
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    /// Given the database path this method will open (or create) it, along with its schema.
+
+    pub fn open(path: &str) -> StoreResult<Self> {
+        if let Some(dirpath) = Path::new(path).parent() {
+            fs::create_dir_all(dirpath)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(PRAGMAS)?;
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(SqliteStore { conn })
+    }
+
+    /// Returns the amount of requests with the given status (`PENDING`, `COMPLETED` or `REJECTED`).
+
+    pub fn count_by_status(&self, status: &str) -> StoreResult<u64> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM request_status WHERE status = ?1",
+            params![status],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+}
+
+impl StatusStore for SqliteStore {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn put(&mut self, req_status: &RequestStatus) -> StoreResult<()> {
+        let req = &req_status.req;
+        self.conn.execute(
+            "INSERT INTO request_status
+                (id, status, airline, origin, destiny, start_time, completed_at, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (id) DO UPDATE SET
+                status = excluded.status,
+                completed_at = excluded.completed_at,
+                data = excluded.data",
+            params![
+                req.id,
+                req_status.status(),
+                req.raw_request.airline,
+                req.raw_request.origin,
+                req.raw_request.destiny,
+                req.start_time,
+                req_status.completed_at,
+                serde_json::to_string(req_status)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get(&self, req_id: &str) -> StoreResult<Option<RequestStatus>> {
+        let data: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT data FROM request_status WHERE id = ?1",
+                params![req_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        match data {
+            Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn fail_pending(&mut self, now: i64, reason: &str) -> StoreResult<usize> {
+        let pending = self
+            .conn
+            .prepare("SELECT data FROM request_status WHERE status = 'PENDING'")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for data in &pending {
+            let mut req_status: RequestStatus = serde_json::from_str(data)?;
+            req_status.rejection = Some(reason.to_string());
+            req_status.completed_at = Some(now);
+            req_status.events.push(RequestEvent {
+                timestamp: now,
+                detail: Some(reason.to_string()),
+                ..RequestEvent::new(EventKind::Rejected)
+            });
+            self.put(&req_status)?;
+        }
+        Ok(pending.len())
+    }
+}
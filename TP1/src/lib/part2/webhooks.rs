@@ -0,0 +1,199 @@
+//! Completion webhooks.
+//!
+//! Requests may carry a `callback_url`: once they are completed (or rejected) their
+//! final status is posted to it, signed with the configured secret. Failed deliveries
+//! are retried with exponential backoff, and every attempt is reported back to the
+//! StatusService so it is visible in the request status.
+
+use std::time::Duration;
+
+use actix::{
+    clock::sleep, Actor, ActorFutureExt, Addr, AsyncContext, Context, Handler, Message,
+    ResponseActFuture, WrapFuture,
+};
+use actix_web::rt::task::spawn_blocking;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+
+use crate::common::{
+    config::WebhooksConfig,
+    http_client::{self, Url},
+    utils::now,
+};
+use crate::part2::{
+    logger::Logger,
+    routes::REQUEST_ID_HEADER,
+    status_service::{DeliveryAttempt, StatusService},
+};
+
+// CONSTANTS ------------------------------------------------------------------
+
+/// Header with the HMAC-SHA256 of the body (`sha256=<hex>`), signed with the webhooks secret.
+pub const SIGNATURE_HEADER: &str = "X-AlGlobo-Signature";
+/// Header with the number of the delivery attempt (starting at 1).
+pub const ATTEMPT_HEADER: &str = "X-AlGlobo-Delivery-Attempt";
+
+// ACTOR ----------------------------------------------------------------------
+
+/// WebhookService is an entity <Actor>. It will be in charge of posting the final
+/// status of the requests to their callback URLs, retrying up to `max_attempts` times.
+
+pub struct WebhookService {
+    config: WebhooksConfig,
+    logger: Addr<Logger>,
+}
+
+impl WebhookService {
+    /// Given a WebhooksConfig and an Addr Logger this method will create a WebhookService entity.
+
+    pub fn new(config: WebhooksConfig, logger: Addr<Logger>) -> Self {
+        WebhookService { config, logger }
+    }
+
+    /// Given a WebhookService addr this method is used to deliver the final status (`body`) of a
+    /// request to its callback URL, reporting every attempt to the given StatusService. If the
+    /// WebhookService can't be reached the delivery is reported as failed, with no retries.
+
+    pub fn deliver(
+        webhooks: &Addr<WebhookService>,
+        req_id: String,
+        correlation_id: Option<String>,
+        callback_url: String,
+        body: String,
+        requester: Addr<StatusService>,
+    ) {
+        let webhooks = webhooks.clone();
+        actix::spawn(async move {
+            let msg = Deliver {
+                req_id: req_id.clone(),
+                correlation_id,
+                callback_url,
+                body,
+                attempt: 1,
+                requester: requester.clone(),
+            };
+            if let Err(err) = webhooks.send(msg).await {
+                StatusService::record_delivery(
+                    &requester,
+                    req_id,
+                    DeliveryAttempt {
+                        attempt: 1,
+                        timestamp: now(),
+                        status_code: None,
+                        error: Some(format!("WebhookService unavailable ({})", err)),
+                        retry_in: None,
+                    },
+                );
+            }
+        });
+    }
+
+    fn sign(&self, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.config.secret.as_bytes())
+            .expect("[CRITICAL] Could not create webhooks HMAC");
+        mac.update(body.as_bytes());
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+}
+
+impl Actor for WebhookService {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _: &mut Self::Context) {
+        Logger::send_to(&self.logger, "[WebhookService] Started".to_string());
+    }
+}
+
+// MESSAGES -------------------------------------------------------------------
+
+/// Message to make a delivery attempt of the final status of a request.
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Deliver {
+    req_id: String,
+    correlation_id: Option<String>,
+    callback_url: String,
+    body: String,
+    attempt: u32,
+    requester: Addr<StatusService>,
+}
+
+// HANDLERS -------------------------------------------------------------------
+
+impl Handler<Deliver> for WebhookService {
+    type Result = ResponseActFuture<Self, ()>;
+
+    fn handle(&mut self, msg: Deliver, _ctx: &mut Context<Self>) -> Self::Result {
+        let url = Url::parse(&msg.callback_url);
+        let signature = self.sign(&msg.body);
+        let attempt = msg.attempt.to_string();
+        let correlation_id = msg.correlation_id.clone();
+        let body = msg.body.clone();
+        let timeout = Duration::from_millis(self.config.timeout);
+
+        // The HTTP client is blocking, so the call runs outside of the actor
+        let post = spawn_blocking(move || {
+            let mut headers = vec![
+                ("Content-Type", "application/json"),
+                (SIGNATURE_HEADER, signature.as_str()),
+                (ATTEMPT_HEADER, attempt.as_str()),
+            ];
+            if let Some(correlation_id) = &correlation_id {
+                headers.push((REQUEST_ID_HEADER, correlation_id.as_str()));
+            }
+            http_client::request_public("POST", &url?, &headers, Some(&body), timeout)
+        });
+
+        Box::pin(post.into_actor(self).then(move |result, me, _ctx| {
+            let (status_code, error) = match result {
+                Ok(Ok(res)) if (200..300).contains(&res.status) => (Some(res.status), None),
+                Ok(Ok(res)) => (Some(res.status), Some(format!("HTTP {}", res.status))),
+                Ok(Err(err)) => (None, Some(err.to_string())),
+                Err(err) => (None, Some(err.to_string())),
+            };
+            let retry_in = match &error {
+                Some(_) if msg.attempt < me.config.max_attempts => {
+                    Some(me.config.backoff(msg.attempt))
+                }
+                _ => None,
+            };
+
+            let outcome = match (&error, retry_in) {
+                (None, _) => String::from("delivered"),
+                (Some(error), Some(retry_in)) => {
+                    format!("failed ({}), retrying in {} ms", error, retry_in)
+                }
+                (Some(error), None) => format!("failed ({}), giving up", error),
+            };
+            Logger::send_to(
+                &me.logger,
+                format!(
+                    "[WebhookService] Delivery {} of request {} to {} {}",
+                    msg.attempt, msg.req_id, msg.callback_url, outcome
+                ),
+            );
+            StatusService::record_delivery(
+                &msg.requester,
+                msg.req_id.clone(),
+                DeliveryAttempt {
+                    attempt: msg.attempt,
+                    timestamp: now(),
+                    status_code,
+                    error,
+                    retry_in,
+                },
+            );
+
+            sleep(Duration::from_millis(retry_in.unwrap_or_default()))
+                .into_actor(me)
+                .map(move |_result, _me, ctx| {
+                    if retry_in.is_some() {
+                        ctx.notify(Deliver {
+                            attempt: msg.attempt + 1,
+                            ..msg
+                        });
+                    }
+                })
+        }))
+    }
+}
@@ -1,43 +1,104 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::time::Duration;
 
 use actix::{
-    clock::sleep, Actor, ActorFutureExt, Addr, Context, Handler, Message, ResponseActFuture,
-    WrapFuture,
+    clock::sleep, fut, Actor, ActorContext, ActorFutureExt, Addr, Context, Handler, Message,
+    ResponseActFuture, Supervised, WrapFuture,
 };
-use rand::Rng;
 
+use crate::common::{
+    inventory::SeatInventory,
+    simulation::{FetchOutcome, Simulation},
+};
 use crate::part2::{
-    dispatcher::{FetchFailed, FetchSucceeded, WebServiceDispatcher},
+    dispatcher::{FetchFailed, FetchRejected, FetchSucceeded, WebServiceDispatcher},
     logger::Logger,
-    request::Request,
+    metrics::MetricsCollector,
+    request::Booking,
 };
 
 // ACTOR ----------------------------------------------------------------------
 
+/// WebService is an entity <Actor> that simulates the webservice of a provider.
+/// Airlines with declared flights also keep their seat inventory, reporting
+/// every change to the MetricsCollector.
+/// It runs supervised: if a fetch crashes the WebService is restarted, failing
+/// the fetches in progress so their dispatcher retries them.
+
 pub struct WebService {
     pub name: String,
-    failure_rate: f64,
-    min_delay: u64,
-    max_delay: u64,
+    simulation: Simulation,
+    inventory: SeatInventory,
+    in_progress: Vec<Book>,
     logger: Addr<Logger>,
+    metrics_collector: Addr<MetricsCollector>,
 }
 
 impl WebService {
     pub fn new(
         name: String,
-        failure_rate: f64,
-        min_delay: u64,
-        max_delay: u64,
+        simulation: Simulation,
+        inventory: SeatInventory,
         logger: Addr<Logger>,
+        metrics_collector: Addr<MetricsCollector>,
     ) -> Self {
         WebService {
             name,
-            failure_rate,
-            min_delay,
-            max_delay,
+            simulation,
+            inventory,
+            in_progress: Vec::new(),
             logger,
+            metrics_collector,
         }
     }
+
+    fn report_inventory(&self) {
+        if self.inventory.is_limited() {
+            MetricsCollector::update_inventory(
+                &self.metrics_collector,
+                self.name.clone(),
+                self.inventory.remaining(),
+            );
+        }
+    }
+
+    /// Replies the outcome of a fetch to its dispatcher. The reply waits for room in the
+    /// dispatcher mailbox, outside of this actor, so it is not lost if the WebService restarts.
+
+    fn reply<M>(&self, requester: &Addr<WebServiceDispatcher>, msg: M)
+    where
+        M: Message<Result = ()> + Send + 'static,
+        WebServiceDispatcher: Handler<M>,
+    {
+        let requester = requester.clone();
+        let logger = self.logger.clone();
+        let name = self.name.clone();
+        actix::spawn(async move {
+            if let Err(err) = requester.send(msg).await {
+                Logger::send_to(
+                    &logger,
+                    format!(
+                        "({}) [WARNING] Could not reply to dispatcher: {}",
+                        name, err
+                    ),
+                );
+            }
+        });
+    }
+
+    /// Fails the given booking (so it is retried) and stops the WebService, to be restarted.
+
+    fn crash(&mut self, Book { booking, requester }: Book, ctx: &mut Context<Self>) {
+        Logger::send_to(
+            &self.logger,
+            format!(
+                "({}) [CRITICAL] WebService crashed fetching for request {}",
+                self.name, booking.req
+            ),
+        );
+        self.reply(&requester, FetchFailed { booking });
+        ctx.stop();
+    }
 }
 
 impl Actor for WebService {
@@ -45,41 +106,120 @@ impl Actor for WebService {
 
     fn started(&mut self, _: &mut Self::Context) {
         Logger::send_to(&self.logger, format!("({}) WebService started", self.name));
+        self.report_inventory();
+    }
+}
+
+impl Supervised for WebService {
+    fn restarting(&mut self, _ctx: &mut Context<Self>) {
+        Logger::send_to(
+            &self.logger,
+            format!(
+                "({}) WebService restarting, failing {} fetches in progress",
+                self.name,
+                self.in_progress.len()
+            ),
+        );
+        // Fetches in progress were dropped along with the context
+        for Book { booking, requester } in std::mem::take(&mut self.in_progress) {
+            self.reply(&requester, FetchFailed { booking });
+        }
     }
 }
 
 // MESSAGES -------------------------------------------------------------------
 
-#[derive(Message)]
+#[derive(Clone, Message)]
 #[rtype(result = "()")]
 pub struct Book {
-    pub req: Request,
+    pub booking: Booking,
     pub requester: Addr<WebServiceDispatcher>,
 }
 
+/// Message to release the seats of a booking made, as its request was rejected.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Release {
+    pub booking: Booking,
+}
+
 // HANDLERS -------------------------------------------------------------------
 
 impl Handler<Book> for WebService {
     type Result = ResponseActFuture<Self, ()>;
 
-    fn handle(&mut self, Book { req, requester }: Book, _ctx: &mut Context<Self>) -> Self::Result {
-        let mut rng = rand::thread_rng();
-        let fetch_time = rng.gen_range(self.min_delay..self.max_delay);
-
-        Box::pin(sleep(Duration::from_secs(fetch_time)).into_actor(self).map(
-            move |_result, me, _ctx| {
-                let coin = rng.gen_range(0.0..1.0);
-
-                if coin > me.failure_rate {
-                    requester
-                        .try_send(FetchSucceeded { req })
-                        .expect("[CRITICAL] Could not send FetchSucceeded msg");
-                } else {
-                    requester
-                        .try_send(FetchFailed { req })
-                        .expect("[CRITICAL] Could not send FetchFailed msg");
+    fn handle(&mut self, book: Book, ctx: &mut Context<Self>) -> Self::Result {
+        let simulation = &mut self.simulation;
+        let FetchOutcome { delay, succeeded } =
+            match catch_unwind(AssertUnwindSafe(|| simulation.next_fetch())) {
+                Ok(outcome) => outcome,
+                Err(_) => {
+                    self.crash(book, ctx);
+                    return Box::pin(fut::ready(()));
                 }
-            },
-        ))
+            };
+        self.in_progress.push(book.clone());
+
+        Box::pin(
+            sleep(Duration::from_secs(delay))
+                .into_actor(self)
+                .map(move |_result, me, ctx| {
+                    me.in_progress
+                        .retain(|in_progress| !in_progress.booking.is_same(&book.booking));
+                    if !succeeded {
+                        me.reply(
+                            &book.requester,
+                            FetchFailed {
+                                booking: book.booking,
+                            },
+                        );
+                        return;
+                    }
+
+                    let route = book.booking.route();
+                    let seats = book.booking.req.raw_request.party_size();
+                    let inventory = &mut me.inventory;
+                    match catch_unwind(AssertUnwindSafe(|| inventory.book(&route, seats))) {
+                        Ok(Ok(())) => {
+                            me.report_inventory();
+                            me.reply(
+                                &book.requester,
+                                FetchSucceeded {
+                                    booking: book.booking,
+                                },
+                            );
+                        }
+                        Ok(Err(reason)) => me.reply(
+                            &book.requester,
+                            FetchRejected {
+                                booking: book.booking,
+                                reason: reason.to_string(),
+                            },
+                        ),
+                        Err(_) => me.crash(book, ctx),
+                    }
+                }),
+        )
+    }
+}
+
+impl Handler<Release> for WebService {
+    type Result = ();
+
+    fn handle(&mut self, Release { booking }: Release, _ctx: &mut Context<Self>) {
+        if !self.inventory.is_limited() {
+            return;
+        }
+        let route = booking.route();
+        let seats = booking.req.raw_request.party_size();
+        self.inventory.release(&route, seats);
+        Logger::send_to(
+            &self.logger,
+            format!(
+                "({}) Released {} seats of {} -> {} for rejected request {}",
+                self.name, seats, route.origin, route.destiny, booking.req
+            ),
+        );
+        self.report_inventory();
     }
 }